@@ -0,0 +1,142 @@
+use crate::clipboard;
+use crate::error::{Error, Result};
+use crate::history::Url;
+use crate::open::{self, OpenOptions};
+use serde::{Deserialize, Serialize};
+
+/// What to do with the selected entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    /// Open in a browser.
+    Open,
+    /// Open the page a search led to, rather than searching again; other entries just open.
+    OpenLanding,
+    /// Copy the URL to the clipboard.
+    Copy,
+    /// Copy a `[title](url)` markdown link to the clipboard.
+    CopyMarkdown,
+    /// Print the URL to stdout.
+    Print,
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(Action::Open),
+            "open-landing" => Ok(Action::OpenLanding),
+            "copy" => Ok(Action::Copy),
+            "copy-markdown" => Ok(Action::CopyMarkdown),
+            "print" => Ok(Action::Print),
+            _ => Err(format!(
+                "unknown action '{}', expected open, open-landing, copy, copy-markdown or print",
+                s
+            )),
+        }
+    }
+}
+
+/// Keys that end the picker with an action other than the one bound to Enter, unless the
+/// config file binds its own.
+pub const DEFAULT_KEYS: &[(&str, Action)] = &[
+    ("ctrl-y", Action::Copy),
+    ("ctrl-t", Action::CopyMarkdown),
+    ("alt-enter", Action::Print),
+    ("alt-o", Action::OpenLanding),
+];
+
+impl Action {
+    /// Whether the action opens the selection in a browser.
+    pub fn opens(self) -> bool {
+        matches!(self, Action::Open | Action::OpenLanding)
+    }
+}
+
+/// Carry out `action` on the selection, in selection order.
+pub fn run(
+    action: Action,
+    selected: &[&Url],
+    open_options: &OpenOptions,
+    warnings: &mut Vec<Error>,
+) -> Result<()> {
+    match action {
+        Action::Open => open::open_all(selected, open_options, warnings),
+        Action::OpenLanding => {
+            let landings: Vec<Url> = selected.iter().map(|u| landing(u)).collect();
+            open::open_all(&landings.iter().collect::<Vec<_>>(), open_options, warnings)
+        }
+        Action::Copy => copy(&lines(selected, |u| u.url.clone())),
+        Action::CopyMarkdown => copy(&lines(selected, markdown_link)),
+        Action::Print => {
+            for u in selected {
+                println!("{}", u.url);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// The page a search led to, from the same source, or a copy of the entry itself.
+fn landing(u: &Url) -> Url {
+    let url = u
+        .search
+        .as_ref()
+        .and_then(|s| s.landing.clone())
+        .unwrap_or_else(|| u.url.clone());
+    Url {
+        url,
+        title: u.title.clone(),
+        sources: u.sources.clone(),
+        ..Url::default()
+    }
+}
+
+fn lines(selected: &[&Url], f: impl Fn(&Url) -> String) -> String {
+    selected.iter().map(|u| f(u)).collect::<Vec<_>>().join("\n")
+}
+
+fn copy(text: &str) -> Result<()> {
+    clipboard::copy(text).map_err(Error::Clipboard)
+}
+
+/// `[title](url)`, escaping what would end the link early and falling back to the URL as text.
+pub fn markdown_link(u: &Url) -> String {
+    let title = u.title.trim();
+    let text = if title.is_empty() { &u.url } else { title };
+    let text = text
+        .replace('\n', " ")
+        .replace('[', "\\[")
+        .replace(']', "\\]");
+    let url = u
+        .url
+        .replace('(', "%28")
+        .replace(')', "%29")
+        .replace(' ', "%20");
+    format!("[{}]({})", text, url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EXIT_ERROR;
+    use crate::open::Target;
+
+    #[test]
+    fn failed_open_is_an_error() {
+        let u = Url {
+            url: "https://example.com/".to_string(),
+            ..Url::default()
+        };
+        let options = OpenOptions {
+            target: Target::Command("/nonexistent/fuhl-opener {url}".to_string()),
+            new_window: false,
+            private: false,
+        };
+        let error = run(Action::Open, &[&u], &options, &mut Vec::new()).unwrap_err();
+        assert!(matches!(error, Error::Open { ref url, .. } if url == "https://example.com/"));
+        assert_eq!(error.exit_code(), EXIT_ERROR);
+        assert!(run(Action::Print, &[&u], &options, &mut Vec::new()).is_ok());
+    }
+}
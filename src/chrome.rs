@@ -0,0 +1,305 @@
+use crate::error::{Error, Result};
+use crate::history::{
+    MAX_VISIT_SAMPLES, SearchDetail, TIMELINE_DAYS, Timeline, Transition, Url, Visit, VisitDetail,
+    attach_timelines, attach_visits, collect_rows, timelines,
+};
+use crate::time::{MICROS_PER_DAY, Timestamp};
+use rusqlite::Connection;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Read the `urls` table of a Chrome (or Chromium-based) `History` database.
+pub fn read_urls(
+    conn: &Connection,
+    since: Timestamp,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    let mut stmt = conn.prepare("SELECT id, url, title, visit_count, typed_count, last_visit_time, hidden FROM urls WHERE last_visit_time >= ?1 ORDER BY last_visit_time DESC, visit_count DESC")?;
+
+    let url_iter = stmt.query_map([since.webkit_micros()], |row| {
+        Ok(Url {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            title: row.get(2)?,
+            visit_count: row.get(3)?,
+            typed_count: row.get(4)?,
+            last_visit_time: Timestamp::from_webkit_micros(row.get(5)?),
+            hidden: row.get(6)?,
+            sources: Vec::new(),
+            bookmark: None,
+            visits: Vec::new(),
+            timeline: Timeline::default(),
+            visit: None,
+            text: None,
+            search: None,
+            tab: None,
+        })
+    })?;
+
+    let mut urls = collect_rows(url_iter, skipped);
+    attach_visits(&mut urls, read_visits(conn, since)?);
+    attach_timelines(&mut urls, read_timelines(conn, since)?);
+    Ok(urls)
+}
+
+/// Read every row of the `visits` table, newest first, with the URL it came from.
+pub fn read_visit_log(
+    conn: &Connection,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    let mut stmt = conn.prepare(
+        "SELECT u.id, u.url, u.title, v.visit_time, v.transition, u.hidden, r.url
+         FROM visits v
+         JOIN urls u ON u.id = v.url
+         LEFT JOIN visits f ON f.id = v.from_visit
+         LEFT JOIN urls r ON r.id = f.url
+         ORDER BY v.visit_time DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Url::visit(
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            Timestamp::from_webkit_micros(row.get(3)?),
+            row.get(5)?,
+            VisitDetail {
+                transition: transition(row.get(4)?),
+                referrer: row.get(6)?,
+            },
+        ))
+    })?;
+    Ok(collect_rows(rows, skipped))
+}
+
+/// Read the `keyword_search_terms` table, with the results page each search went to and the
+/// page last reached from it.
+pub fn read_searches(
+    conn: &Connection,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    let mut stmt = conn.prepare(
+        "SELECT u.id, u.url, k.term, k.normalized_term, u.visit_count, u.last_visit_time,
+             u.hidden,
+             (SELECT l.url FROM visits s
+              JOIN visits v ON v.from_visit = s.id
+              JOIN urls l ON l.id = v.url
+              WHERE s.url = u.id AND l.id != u.id
+              ORDER BY v.visit_time DESC LIMIT 1)
+         FROM keyword_search_terms k
+         JOIN urls u ON u.id = k.url_id
+         ORDER BY u.last_visit_time DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Url {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            title: row.get(2)?,
+            visit_count: row.get(4)?,
+            last_visit_time: Timestamp::from_webkit_micros(row.get(5)?),
+            hidden: row.get(6)?,
+            search: Some(SearchDetail {
+                term: row.get(3)?,
+                landing: row.get(7)?,
+            }),
+            ..Url::default()
+        })
+    })?;
+    Ok(collect_rows(rows, skipped))
+}
+
+/// First visit and recent per-day visit counts for every URL, from the `visits` table.
+fn read_timelines(conn: &Connection, since: Timestamp) -> rusqlite::Result<HashMap<i64, Timeline>> {
+    let mut first = conn.prepare(
+        "SELECT url, MIN(visit_time) FROM visits
+         WHERE url IN (SELECT id FROM urls WHERE last_visit_time >= ?1)
+         GROUP BY url",
+    )?;
+    let mut daily = conn.prepare(
+        "SELECT url, visit_time / ?1 AS day, COUNT(*) FROM visits
+         WHERE visit_time >= ?2 AND url IN (SELECT id FROM urls WHERE last_visit_time >= ?3)
+         GROUP BY url, day",
+    )?;
+    let start = Timestamp::now().webkit_micros() - TIMELINE_DAYS * MICROS_PER_DAY;
+    timelines(
+        first.query_map([since.webkit_micros()], |row| {
+            Ok((row.get(0)?, Timestamp::from_webkit_micros(row.get(1)?)))
+        })?,
+        daily.query_map([MICROS_PER_DAY, start, since.webkit_micros()], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?,
+    )
+}
+
+/// Sample the most recent rows of the `visits` table for every URL.
+fn read_visits(conn: &Connection, since: Timestamp) -> rusqlite::Result<HashMap<i64, Vec<Visit>>> {
+    let mut stmt = conn.prepare(
+        "SELECT url, visit_time, transition FROM (
+             SELECT url, visit_time, transition,
+                    ROW_NUMBER() OVER (PARTITION BY url ORDER BY visit_time DESC) AS n
+             FROM visits
+             WHERE url IN (SELECT id FROM urls WHERE last_visit_time >= ?2)
+         )
+         WHERE n <= ?1
+         ORDER BY url, visit_time DESC",
+    )?;
+    let mut rows = stmt.query([MAX_VISIT_SAMPLES as i64, since.webkit_micros()])?;
+
+    let mut visits: HashMap<i64, Vec<Visit>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        visits.entry(row.get(0)?).or_default().push(Visit {
+            time: Timestamp::from_webkit_micros(row.get(1)?),
+            transition: transition(row.get(2)?),
+        });
+    }
+    Ok(visits)
+}
+
+/// Map a Chrome `ui::PageTransition` value onto [`Transition`].
+pub fn transition(value: i64) -> Transition {
+    // The low byte is the core type; the high bits are qualifiers. A server or client redirect
+    // keeps the core type of the navigation it ended, so it is checked first
+    const CHAIN_REDIRECT_MASK: i64 = 0xC000_0000;
+    if value & CHAIN_REDIRECT_MASK != 0 {
+        return Transition::Redirect;
+    }
+    match value & 0xFF {
+        0 | 7 => Transition::Link,
+        1 | 5 | 9 | 10 => Transition::Typed,
+        2 => Transition::Bookmark,
+        8 => Transition::Reload,
+        _ => Transition::Other,
+    }
+}
+
+/// Read the JSON `Bookmarks` file that sits next to a Chrome profile's `History` database.
+/// A profile without bookmarks has no such file.
+pub fn read_bookmarks(path: &Path) -> Result<Vec<Url>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => {
+            return Err(Error::Io {
+                path: path.to_path_buf(),
+                source,
+            });
+        }
+    };
+    let json: Value = serde_json::from_str(&contents).map_err(|source| Error::Bookmarks {
+        path: path.to_path_buf(),
+        source,
+    })?;
+
+    let mut bookmarks = Vec::new();
+    if let Some(roots) = json.get("roots").and_then(Value::as_object) {
+        for root in roots.values() {
+            collect_bookmarks(root, "", &mut bookmarks);
+        }
+    }
+    Ok(bookmarks)
+}
+
+fn collect_bookmarks(node: &Value, parent: &str, bookmarks: &mut Vec<Url>) {
+    let name = node.get("name").and_then(Value::as_str).unwrap_or_default();
+    match node.get("type").and_then(Value::as_str) {
+        Some("url") => {
+            let Some(url) = node.get("url").and_then(Value::as_str) else {
+                return;
+            };
+            bookmarks.push(Url {
+                id: 0,
+                url: url.to_string(),
+                title: name.to_string(),
+                visit_count: 0,
+                typed_count: 0,
+                last_visit_time: Timestamp::default(),
+                hidden: 0,
+                sources: Vec::new(),
+                bookmark: Some(parent.to_string()),
+                visits: Vec::new(),
+                timeline: Timeline::default(),
+                visit: None,
+                text: None,
+                search: None,
+                tab: None,
+            });
+        }
+        Some("folder") => {
+            let folder = if parent.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}/{}", parent, name)
+            };
+            let children = node.get("children").and_then(Value::as_array);
+            for child in children.into_iter().flatten() {
+                collect_bookmarks(child, &folder, bookmarks);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{self, Browser};
+
+    #[test]
+    fn redirects_count_whatever_started_them() {
+        assert_eq!(transition(0x8000_0001), Transition::Redirect);
+        assert_eq!(transition(0x4000_0002), Transition::Redirect);
+        assert_eq!(transition(0x8000_0000), Transition::Redirect);
+        // Chain start and end qualifiers alone are not redirects
+        assert_eq!(transition(0x3000_0001), Transition::Typed);
+        assert_eq!(transition(0x0100_0000 | 2), Transition::Bookmark);
+        assert_eq!(transition(8), Transition::Reload);
+    }
+
+    fn fixture() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE urls (id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR,
+                 visit_count INTEGER, typed_count INTEGER, last_visit_time INTEGER,
+                 hidden INTEGER);
+             CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER, visit_time INTEGER,
+                 from_visit INTEGER, transition INTEGER);
+             CREATE TABLE keyword_search_terms (keyword_id INTEGER, url_id INTEGER,
+                 term LONGVARCHAR, normalized_term LONGVARCHAR);
+             INSERT INTO urls VALUES (1, 'https://www.google.com/search?q=Rust+FTS5', '', 1, 0, 100, 0);
+             INSERT INTO urls VALUES (2, 'https://sqlite.org/fts5.html', 'FTS5', 1, 0, 110, 0);
+             INSERT INTO urls VALUES (3, 'https://www.google.com/search?q=rust+fts5', '', 2, 0, 300, 0);
+             INSERT INTO urls VALUES (4, 'https://duckduckgo.com/?q=skim', '', 1, 0, 200, 0);
+             INSERT INTO visits VALUES (1, 1, 100, 0, 1);
+             INSERT INTO visits VALUES (2, 2, 110, 1, 0);
+             INSERT INTO visits VALUES (3, 3, 300, 0, 1);
+             INSERT INTO visits VALUES (4, 4, 200, 0, 1);
+             INSERT INTO keyword_search_terms VALUES (2, 1, 'Rust FTS5', 'rust fts5');
+             INSERT INTO keyword_search_terms VALUES (2, 3, 'rust fts5', 'rust fts5');
+             INSERT INTO keyword_search_terms VALUES (5, 4, 'skim', 'skim');",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn repeated_searches_keep_where_they_led() {
+        let conn = fixture();
+        let searches = history::merge_searches(
+            Browser::Chrome
+                .read_searches(&conn, &mut Vec::new())
+                .unwrap(),
+        );
+        assert_eq!(searches.len(), 2);
+
+        let rust = &searches[0];
+        assert_eq!(rust.url, "https://www.google.com/search?q=rust+fts5");
+        assert_eq!(rust.title, "rust fts5");
+        assert_eq!(rust.visit_count, 3);
+        // The newest search led nowhere, so the older one's landing page is kept
+        assert_eq!(
+            rust.search.as_ref().unwrap().landing.as_deref(),
+            Some("https://sqlite.org/fts5.html")
+        );
+        assert_eq!(searches[1].search.as_ref().unwrap().landing, None);
+    }
+}
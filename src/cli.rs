@@ -0,0 +1,442 @@
+use crate::shell::Shell;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand};
+use fuhl::action::Action;
+use fuhl::config::{Config, FilterConfig};
+use fuhl::open::Target;
+use fuhl::rank::SortOrder;
+use fuhl::search::Query;
+use fuhl::source::Mode;
+use fuhl::time::{self, Timestamp};
+use regex::Regex;
+use std::path::PathBuf;
+
+/// Fuzzy Url History Launcher: pick a page from your browser history and open it.
+///
+/// Settings are read from ~/.config/fuhl/config.toml (or $FUHL_CONFIG), then from environment
+/// variables, then from these flags; `fuhl config show` prints the result.
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    after_help = "Exit status: 0 on success, 1 when nothing matches, 2 on errors, and 130 when the \
+                  picker is closed without a selection."
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Search terms to start the picker with. Put them after `--` when the first is also the name
+    /// of a command, as in `fuhl -- config`
+    pub query: Vec<String>,
+
+    /// Open the only match straight away instead of showing the picker
+    #[arg(short = '1', long)]
+    pub select_1: bool,
+
+    /// Exit straight away when nothing matches
+    #[arg(short = '0', long)]
+    pub exit_0: bool,
+
+    /// Select several entries with Tab
+    #[arg(short, long)]
+    pub multi: bool,
+
+    /// History database to read instead of the discovered ones; may be given several times
+    #[arg(long, value_name = "PATH")]
+    pub db: Vec<PathBuf>,
+
+    /// Height of the picker, in lines or as a percentage of the terminal
+    #[arg(long, env = "FUHL_HEIGHT")]
+    pub height: Option<String>,
+
+    /// What Enter does with the selection: open, open-landing, copy, copy-markdown or print.
+    /// In the picker Ctrl-Y copies, Ctrl-T copies a markdown link and Alt-Enter prints
+    #[arg(long, env = "FUHL_ACTION")]
+    pub action: Option<Action>,
+
+    /// Browser to open the selection in: default, source (the browser and profile each entry
+    /// came from), or a browser name such as chrome, firefox or brave
+    #[arg(long, value_name = "BROWSER", env = "FUHL_OPEN_IN")]
+    pub open_in: Option<Target>,
+
+    /// Command to open each URL with instead of a browser, such as 'firefox --new-window {url}'
+    #[arg(long, value_name = "COMMAND", env = "FUHL_OPEN_WITH")]
+    pub open_with: Option<String>,
+
+    /// Whether --open-in takes precedence over --open-with, see [`parse`]
+    #[arg(skip)]
+    pub open_in_wins: bool,
+
+    /// Open the selection together in one new window instead of new tabs
+    #[arg(long)]
+    pub new_window: bool,
+
+    /// Open the selection in a private (incognito) window
+    #[arg(long)]
+    pub private: bool,
+
+    /// Never open more than N URLs at once [default: 20]
+    #[arg(long, value_name = "N")]
+    pub max_open: Option<usize>,
+
+    /// Ask before opening more than N URLs [default: 5]
+    #[arg(long, value_name = "N")]
+    pub confirm_above: Option<usize>,
+
+    /// Print matches for QUERY, and any search terms, to stdout instead of showing the picker
+    #[arg(long = "filter", value_name = "QUERY")]
+    pub filter_query: Option<String>,
+
+    /// Keep only pages matching a full-text QUERY: words, "phrases", prefix*, -excluded,
+    /// title: or url: qualifiers and OR, as in 'title:"incident review" -staging'
+    #[arg(long = "match", value_name = "QUERY", allow_hyphen_values = true)]
+    pub match_query: Option<Query>,
+
+    /// Print matches for the search terms to stdout instead of showing the picker
+    #[arg(long)]
+    pub print: bool,
+
+    /// Print at most N matches
+    #[arg(long, value_name = "N")]
+    pub limit: Option<usize>,
+
+    /// Template for each printed match, using {url}, {title}, {source}, {folder},
+    /// {visit_count}, {typed_count}, {last_visit_time}, {tab} or {line}, for visits {transition}
+    /// or {referrer}, and for searches {landing} [default: {url}]
+    #[arg(long, env = "FUHL_FORMAT")]
+    pub format: Option<String>,
+
+    /// Order of entries: frecency or recent [default: frecency]
+    #[arg(long, env = "FUHL_SORT")]
+    pub sort: Option<SortOrder>,
+
+    /// List every visit, newest first, with how it was reached and from where
+    #[arg(long)]
+    pub visits: bool,
+
+    /// List past searches typed into search engines, newest first. Enter searches again and
+    /// Alt-O opens the page the search led to
+    #[arg(long, conflicts_with = "visits")]
+    pub searches: bool,
+
+    /// Read the browsers directly instead of through fuhl's index
+    #[arg(long)]
+    pub no_index: bool,
+
+    /// Match the text stored by `fuhl text fetch` as well, exactly rather than fuzzily
+    #[arg(long)]
+    pub page_text: bool,
+
+    #[command(flatten)]
+    pub filter: FilterArgs,
+}
+
+#[derive(Debug, clap::Args)]
+#[command(next_help_heading = "Filters")]
+pub struct FilterArgs {
+    /// Drop URLs longer than this many characters
+    #[arg(long, value_name = "N")]
+    pub max_length: Option<usize>,
+
+    /// Keep only URLs matching REGEX
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    pub include_url: Vec<Regex>,
+
+    /// Drop URLs matching REGEX
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    pub exclude_url: Vec<Regex>,
+
+    /// Keep only titles matching REGEX
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    pub include_title: Vec<Regex>,
+
+    /// Drop titles matching REGEX
+    #[arg(long, value_name = "REGEX", value_parser = Regex::new)]
+    pub exclude_title: Vec<Regex>,
+
+    /// Include rows the browser marked hidden
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Drop URLs with this scheme
+    #[arg(long, value_name = "SCHEME", value_parser = scheme)]
+    pub exclude_scheme: Vec<String>,
+
+    /// Keep URLs with a scheme dropped by default, such as file
+    #[arg(long, value_name = "SCHEME", value_parser = scheme)]
+    pub allow_scheme: Vec<String>,
+
+    /// Keep URLs visited since WHEN: an age such as 2d, 3h or 1w, or a date such as 2026-01-01
+    #[arg(long, value_name = "WHEN", value_parser = when)]
+    pub since: Option<Timestamp>,
+
+    /// Keep URLs last visited before WHEN, in the same forms as --since
+    #[arg(long, value_name = "WHEN", value_parser = when)]
+    pub before: Option<Timestamp>,
+
+    /// Keep URLs visited since local midnight
+    #[arg(long, conflicts_with = "since")]
+    pub today: bool,
+
+    /// Keep only pages open in a browser tab or in one closed recently
+    #[arg(long, conflicts_with_all = ["visits", "searches"])]
+    pub tabs: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Inspect the settings
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Manage fuhl's index of browser history
+    Index {
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+    /// Manage the stored text of pages, which makes them searchable by what they say
+    Text {
+        #[command(subcommand)]
+        command: TextCommand,
+    },
+    /// Print a shell widget that binds Ctrl-X Ctrl-U to insert picked URLs at the cursor
+    ///
+    /// Load it from the shell's startup file, for example with eval "$(fuhl init bash)" or
+    /// fuhl init fish | source.
+    Init {
+        /// The shell to print the widget for: bash, zsh or fish
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective settings, in the form of the config file
+    Show,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum IndexCommand {
+    /// Read everything new from the browsers now, even if their databases look unchanged
+    Sync,
+    /// Delete the index, including any history kept with `keep_expired` and stored page text
+    Clear,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TextCommand {
+    /// Download pages and store their readable text
+    Fetch {
+        /// Pages to fetch, in addition to those chosen by --bookmarks and --min-visits
+        #[arg(required_unless_present_any = ["bookmarks", "min_visits"])]
+        urls: Vec<String>,
+
+        /// Fetch every bookmarked page
+        #[arg(long)]
+        bookmarks: bool,
+
+        /// Fetch pages visited at least N times
+        #[arg(long, value_name = "N")]
+        min_visits: Option<i64>,
+
+        /// Fetch pages again even if their text is already stored
+        #[arg(long)]
+        refetch: bool,
+    },
+    /// Store the descriptions Firefox keeps of pages that have no text stored yet
+    Import,
+    /// Delete all stored page text
+    Clear,
+}
+
+/// Parse the command line and environment, exiting with a usage error if they are invalid.
+pub fn parse() -> Args {
+    from_matches(&Args::command().get_matches())
+}
+
+fn from_matches(matches: &ArgMatches) -> Args {
+    let mut args = Args::from_arg_matches(matches).unwrap_or_else(|e| e.exit());
+    // A flag beats an environment variable, and of two flags the later one wins
+    let rank = |id: &str| match matches.value_source(id) {
+        Some(ValueSource::CommandLine) => (true, matches.index_of(id)),
+        _ => (false, None),
+    };
+    args.open_in_wins = rank("open_in") > rank("open_with");
+    args
+}
+
+impl Args {
+    /// Whether to print matches rather than run the picker.
+    pub fn headless(&self) -> bool {
+        self.print || self.filter_query.is_some()
+    }
+
+    /// Override `config` with the flags and environment variables that were given.
+    pub fn apply(&self, config: &mut Config) {
+        if !self.db.is_empty() {
+            config.sources = self.db.clone();
+        }
+        if let Some(height) = &self.height {
+            config.picker.height = height.clone();
+        }
+        config.picker.multi |= self.multi;
+        if let Some(format) = &self.format {
+            config.display.format = format.clone();
+        }
+        if let Some(sort) = self.sort {
+            config.rank.sort = sort;
+        }
+        if let Some(action) = self.action {
+            config.open.action = action;
+        }
+        // A browser and a command exclude each other, so apply the one that takes precedence last
+        let open_in = |config: &mut Config| {
+            if let Some(browser) = &self.open_in {
+                config.open.browser = browser.clone();
+                config.open.command = None;
+            }
+        };
+        let open_with = |config: &mut Config| {
+            if let Some(command) = &self.open_with {
+                config.open.command = Some(command.clone());
+            }
+        };
+        if self.open_in_wins {
+            open_with(config);
+            open_in(config);
+        } else {
+            open_in(config);
+            open_with(config);
+        }
+        config.open.new_window |= self.new_window;
+        config.open.private |= self.private;
+        if let Some(max_open) = self.max_open {
+            config.open.max_open = max_open;
+        }
+        if let Some(confirm_above) = self.confirm_above {
+            config.open.confirm_above = confirm_above;
+        }
+        config.index.enabled &= !self.no_index;
+        config.picker.page_text |= self.page_text;
+        self.filter.apply(&mut config.filter);
+    }
+
+    /// What to list: pages, or with --visits or --searches those instead.
+    pub fn mode(&self) -> Mode {
+        if self.visits {
+            Mode::Visits
+        } else if self.searches {
+            Mode::Searches
+        } else {
+            Mode::Pages
+        }
+    }
+
+    /// The `--filter` query and the search terms as a single skim query, if any were given.
+    pub fn query(&self) -> Option<String> {
+        let terms: Vec<&str> = self
+            .filter_query
+            .iter()
+            .chain(&self.query)
+            .map(String::as_str)
+            .collect();
+        if terms.is_empty() {
+            None
+        } else {
+            Some(terms.join(" "))
+        }
+    }
+}
+
+impl FilterArgs {
+    /// Add these patterns and schemes to the configured ones.
+    fn apply(&self, filter: &mut FilterConfig) {
+        if self.max_length.is_some() {
+            filter.max_length = self.max_length;
+        }
+        filter.include_url.extend(patterns(&self.include_url));
+        filter.exclude_url.extend(patterns(&self.exclude_url));
+        filter.include_title.extend(patterns(&self.include_title));
+        filter.exclude_title.extend(patterns(&self.exclude_title));
+        filter.hidden |= self.hidden;
+        for scheme in &self.exclude_scheme {
+            if !filter.exclude_schemes.contains(scheme) {
+                filter.exclude_schemes.push(scheme.clone());
+            }
+        }
+        filter
+            .exclude_schemes
+            .retain(|s| !self.allow_scheme.contains(s));
+    }
+
+    /// The `--since`/`--today` and `--before` bounds on the last visit.
+    pub fn time_range(&self) -> (Option<Timestamp>, Option<Timestamp>) {
+        let since = if self.today {
+            Some(time::start_of_today())
+        } else {
+            self.since
+        };
+        (since, self.before)
+    }
+}
+
+fn patterns(regexes: &[Regex]) -> impl Iterator<Item = String> + '_ {
+    regexes.iter().map(|r| r.as_str().to_string())
+}
+
+fn when(value: &str) -> Result<Timestamp, String> {
+    time::parse_when(value, Timestamp::now())
+}
+
+/// Normalise `chrome://` or `Chrome` to `chrome`.
+fn scheme(value: &str) -> Result<String, String> {
+    Ok(value.trim_end_matches("://").to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_for(args: &[&str]) -> Config {
+        let args = from_matches(&Args::command().get_matches_from(args));
+        let mut config = Config::default();
+        args.apply(&mut config);
+        config
+    }
+
+    #[test]
+    fn later_open_flag_wins() {
+        let config = config_for(&["fuhl", "--open-with", "echo {url}", "--open-in", "firefox"]);
+        assert_eq!(config.open.browser, Target::Browser("Firefox".to_string()));
+        assert_eq!(config.open.command, None);
+
+        let config = config_for(&["fuhl", "--open-in", "firefox", "--open-with", "echo {url}"]);
+        assert_eq!(config.open.command.as_deref(), Some("echo {url}"));
+    }
+
+    #[test]
+    fn command_names_are_queries_after_a_double_dash() {
+        let parse = |args: &[&str]| from_matches(&Args::command().get_matches_from(args));
+        let args = parse(&["fuhl", "--print", "--", "config", "init"]);
+        assert!(args.command.is_none());
+        assert_eq!(args.query, ["config", "init"]);
+
+        let args = parse(&["fuhl", "config", "show"]);
+        assert!(matches!(args.command, Some(Command::Config { .. })));
+        assert!(args.query.is_empty());
+    }
+
+    #[test]
+    fn filter_query_keeps_the_search_terms() {
+        let parse = |args: &[&str]| from_matches(&Args::command().get_matches_from(args));
+        let args = parse(&["fuhl", "--filter", "grafana", "prod", "cpu"]);
+        assert!(args.headless());
+        assert_eq!(args.query().as_deref(), Some("grafana prod cpu"));
+        assert_eq!(
+            parse(&["fuhl", "--filter", "grafana"]).query().as_deref(),
+            Some("grafana")
+        );
+        assert_eq!(parse(&["fuhl"]).query(), None);
+    }
+}
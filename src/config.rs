@@ -0,0 +1,311 @@
+use crate::action::{Action, DEFAULT_KEYS};
+use crate::error::{Error, Result};
+use crate::filter::{DEFAULT_EXCLUDED_SCHEMES, Filter};
+use crate::index::Index;
+use crate::open::{OpenOptions, Target};
+use crate::rank::{SortOrder, Weights};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Effective settings: built-in defaults, overridden by the config file, then by environment
+/// variables, then by command line flags.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// History databases to read; when empty they are discovered.
+    pub sources: Vec<PathBuf>,
+    pub picker: PickerConfig,
+    pub display: DisplayConfig,
+    pub filter: FilterConfig,
+    pub rank: RankConfig,
+    pub open: OpenConfig,
+    pub index: IndexConfig,
+    /// Picker keys and the action each runs instead of the one bound to Enter.
+    pub keys: BTreeMap<String, Action>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PickerConfig {
+    pub height: String,
+    pub preview_window: String,
+    pub multi: bool,
+    /// Match the stored text of pages as well, exactly rather than fuzzily.
+    pub page_text: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplayConfig {
+    /// Template for `--print` and `--filter` output, see [`crate::display::render`].
+    pub format: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FilterConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_length: Option<usize>,
+    pub include_url: Vec<String>,
+    pub exclude_url: Vec<String>,
+    pub include_title: Vec<String>,
+    pub exclude_title: Vec<String>,
+    pub hidden: bool,
+    pub exclude_schemes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RankConfig {
+    pub sort: SortOrder,
+    pub weights: Weights,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenConfig {
+    /// What Enter does with the selection.
+    pub action: Action,
+    pub browser: Target,
+    /// Command to open each URL with instead of `browser`, with `{url}` for the URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    pub new_window: bool,
+    pub private: bool,
+    pub max_open: usize,
+    pub confirm_above: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexConfig {
+    /// Read history through fuhl's own index rather than straight from the browsers.
+    pub enabled: bool,
+    /// Where the index lives, by default [`Index::default_path`].
+    pub path: PathBuf,
+    /// Keep pages the browsers have expired or deleted, see [`Index::keep_expired`].
+    pub keep_expired: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            sources: Vec::new(),
+            picker: PickerConfig::default(),
+            display: DisplayConfig::default(),
+            filter: FilterConfig::default(),
+            rank: RankConfig::default(),
+            open: OpenConfig::default(),
+            index: IndexConfig::default(),
+            keys: DEFAULT_KEYS
+                .iter()
+                .map(|(key, action)| (key.to_string(), *action))
+                .collect(),
+        }
+    }
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        IndexConfig {
+            enabled: true,
+            path: Index::default_path(),
+            keep_expired: false,
+        }
+    }
+}
+
+impl Default for PickerConfig {
+    fn default() -> Self {
+        PickerConfig {
+            height: "50%".to_string(),
+            preview_window: "right:50%:wrap".to_string(),
+            multi: false,
+            page_text: false,
+        }
+    }
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        DisplayConfig {
+            format: "{url}".to_string(),
+        }
+    }
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            max_length: None,
+            include_url: Vec::new(),
+            exclude_url: Vec::new(),
+            include_title: Vec::new(),
+            exclude_title: Vec::new(),
+            hidden: false,
+            exclude_schemes: DEFAULT_EXCLUDED_SCHEMES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl Default for OpenConfig {
+    fn default() -> Self {
+        OpenConfig {
+            action: Action::Open,
+            browser: Target::Default,
+            command: None,
+            new_window: false,
+            private: false,
+            max_open: 20,
+            confirm_above: 5,
+        }
+    }
+}
+
+impl Config {
+    /// `$FUHL_CONFIG`, else `$XDG_CONFIG_HOME/fuhl/config.toml`, else `~/.config/fuhl/config.toml`.
+    pub fn path() -> PathBuf {
+        if let Some(path) = std::env::var_os("FUHL_CONFIG") {
+            return PathBuf::from(path);
+        }
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .unwrap_or_else(|| PathBuf::from(shellexpand::tilde("~/.config").to_string()));
+        config_home.join("fuhl").join("config.toml")
+    }
+
+    /// The config file at [`Config::path`] over the defaults, or the defaults if there is none.
+    pub fn load() -> Result<Config> {
+        let path = Config::path();
+        let parsed = match std::fs::read_to_string(&path) {
+            Ok(text) => Config::parse(&text),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(e.to_string()),
+        };
+        parsed.map_err(|message| Error::Config { path, message })
+    }
+
+    fn parse(text: &str) -> Result<Config, String> {
+        let mut config: Config = toml::from_str(text).map_err(|e| e.to_string())?;
+        config.filter.to_filter().map_err(|e| e.to_string())?;
+        config.rank.weights.validate()?;
+        for source in &mut config.sources {
+            *source = PathBuf::from(shellexpand::tilde(&source.to_string_lossy()).to_string());
+        }
+        config.index.path =
+            PathBuf::from(shellexpand::tilde(&config.index.path.to_string_lossy()).to_string());
+        Ok(config)
+    }
+
+    /// Apply the environment: `FUHL_DB` lists history databases, separated like `PATH` entries.
+    ///
+    /// Other variables are read along with the flags they stand in for.
+    pub fn apply_env(&mut self) {
+        if let Some(paths) = std::env::var_os("FUHL_DB") {
+            self.sources = std::env::split_paths(&paths).collect();
+        }
+    }
+
+    /// The settings as TOML, in the same form as the config file.
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("Failed to serialize settings")
+    }
+
+    pub fn open_options(&self) -> OpenOptions {
+        let target = match &self.open.command {
+            Some(command) => Target::Command(command.clone()),
+            None => self.open.browser.clone(),
+        };
+        OpenOptions {
+            target,
+            new_window: self.open.new_window,
+            private: self.open.private,
+        }
+    }
+
+    /// The action bound to a key passed to skim's `expect`.
+    pub fn action_for_key(&self, key: &str) -> Option<Action> {
+        self.keys.get(key).copied()
+    }
+}
+
+impl FilterConfig {
+    pub fn to_filter(&self) -> Result<Filter, regex::Error> {
+        let regexes = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Filter {
+            max_length: self.max_length,
+            include_url: regexes(&self.include_url)?,
+            include_title: regexes(&self.include_title)?,
+            exclude_url: regexes(&self.exclude_url)?,
+            exclude_title: regexes(&self.exclude_title)?,
+            show_hidden: self.hidden,
+            excluded_schemes: self.exclude_schemes.clone(),
+            since: None,
+            before: None,
+            tabs: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_file_keeps_defaults() {
+        let config = Config::parse(
+            "[rank]
+             sort = \"recent\"
+             [rank.weights]
+             typed = 3.0
+             [open]
+             browser = \"firefox\"",
+        )
+        .unwrap();
+        assert_eq!(config.rank.sort, SortOrder::Recent);
+        assert_eq!(config.rank.weights.typed, 3.0);
+        assert_eq!(config.rank.weights.half_life_days, 14.0);
+        assert_eq!(config.open.browser, Target::Browser("Firefox".to_string()));
+        assert_eq!(config.open.max_open, 20);
+        assert_eq!(config.picker.height, "50%");
+        assert_eq!(config.action_for_key("ctrl-y"), Some(Action::Copy));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bad_regexes() {
+        assert!(Config::parse("[picker]\nhieght = \"40%\"").is_err());
+        assert!(Config::parse("[filter]\nexclude_url = [\"(\"]").is_err());
+        assert!(Config::parse("[open]\naction = \"launch\"").is_err());
+    }
+
+    #[test]
+    fn rejects_negative_weights_and_half_lives() {
+        let error = Config::parse("[rank.weights]\nlink = -1.0").unwrap_err();
+        assert!(error.contains("rank.weights.link"), "{}", error);
+        assert!(Config::parse("[rank.weights]\nhalf_life_days = 0.0").is_err());
+        assert!(Config::parse("[rank.weights]\nhalf_life_days = -7.0").is_err());
+        assert!(Config::parse("[rank.weights]\nreload = 0.0").is_ok());
+    }
+
+    #[test]
+    fn shown_settings_parse_back() {
+        let mut config = Config::default();
+        config.open.command = Some("firefox --new-window {url}".to_string());
+        config.filter.max_length = Some(200);
+        let shown = Config::parse(&config.to_toml()).unwrap();
+        assert_eq!(shown.open.command, config.open.command);
+        assert_eq!(shown.filter.max_length, Some(200));
+        assert_eq!(shown.keys, config.keys);
+    }
+}
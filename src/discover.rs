@@ -0,0 +1,338 @@
+use std::path::{Path, PathBuf};
+
+/// A browser history database found on disk.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    /// Browser name, or `None` when it has to be worked out from the database schema.
+    pub browser: Option<&'static str>,
+    pub profile: String,
+    pub path: PathBuf,
+}
+
+impl Profile {
+    /// A database given explicitly by the user, named after the directory that holds it.
+    pub fn from_path(path: PathBuf) -> Profile {
+        let profile = path
+            .parent()
+            .and_then(|dir| dir.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Profile {
+            browser: None,
+            profile,
+            path,
+        }
+    }
+}
+
+/// Chromium-based browsers as (name, user data directory relative to `~/.config`).
+const CHROMIUM_BROWSERS: &[(&str, &str)] = &[
+    ("Chrome", "google-chrome"),
+    ("Chrome Beta", "google-chrome-beta"),
+    ("Chrome Dev", "google-chrome-unstable"),
+    ("Chromium", "chromium"),
+    ("Brave", "BraveSoftware/Brave-Browser"),
+    ("Vivaldi", "vivaldi"),
+    ("Edge", "microsoft-edge"),
+    ("Opera", "opera"),
+];
+
+/// Flatpak application ids and the Chromium user data directory inside their sandbox.
+const FLATPAK_CHROMIUM: &[(&str, &str, &str)] = &[
+    ("Chrome", "com.google.Chrome", "google-chrome"),
+    ("Chromium", "org.chromium.Chromium", "chromium"),
+    ("Brave", "com.brave.Browser", "BraveSoftware/Brave-Browser"),
+    ("Vivaldi", "com.vivaldi.Vivaldi", "vivaldi"),
+    ("Edge", "com.microsoft.Edge", "microsoft-edge"),
+    ("Opera", "com.opera.Opera", "opera"),
+];
+
+/// Snap names and the Chromium user data directory inside their sandbox.
+const SNAP_CHROMIUM: &[(&str, &str)] = &[
+    ("Chromium", "chromium/common/chromium"),
+    ("Brave", "brave/current/.config/BraveSoftware/Brave-Browser"),
+    ("Opera", "opera/current/.config/opera"),
+];
+
+/// Find every history database for the current user, newest first.
+pub fn history_databases() -> Vec<Profile> {
+    let mut found = Vec::new();
+    if cfg!(target_os = "macos") {
+        let path = expand("~/Library/Application Support/Google/Chrome/Default/History");
+        if path.exists() {
+            found.push(Profile {
+                browser: Some("Chrome"),
+                profile: "Default".to_string(),
+                path,
+            });
+        }
+        let path = expand("~/Library/Safari/History.db");
+        if path.exists() {
+            found.push(Profile {
+                browser: Some("Safari"),
+                profile: String::new(),
+                path,
+            });
+        }
+    } else if cfg!(target_os = "linux") {
+        linux_profiles(&expand("~"), &mut found);
+    }
+
+    found.sort_by_key(|p| std::cmp::Reverse(modified(&p.path)));
+    found
+}
+
+/// Collect the profiles of every supported browser under the home directory `home`, whether
+/// installed natively, as a Flatpak or as a Snap.
+fn linux_profiles(home: &Path, found: &mut Vec<Profile>) {
+    for (browser, dir) in CHROMIUM_BROWSERS {
+        chromium_profiles(browser, &home.join(".config").join(dir), found);
+    }
+    firefox_profiles(&home.join(".mozilla/firefox"), found);
+
+    for (browser, app, dir) in FLATPAK_CHROMIUM {
+        let config = home.join(".var/app").join(app).join("config");
+        chromium_profiles(browser, &config.join(dir), found);
+    }
+    firefox_profiles(
+        &home.join(".var/app/org.mozilla.firefox/.mozilla/firefox"),
+        found,
+    );
+
+    for (browser, dir) in SNAP_CHROMIUM {
+        chromium_profiles(browser, &home.join("snap").join(dir), found);
+    }
+    firefox_profiles(&home.join("snap/firefox/common/.mozilla/firefox"), found);
+}
+
+fn expand(path: &str) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(path).to_string())
+}
+
+fn modified(path: &Path) -> Option<std::time::SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Collect `History` files from a Chromium user data directory.
+///
+/// Profiles live in subdirectories (`Default`, `Profile 1`, ...), except for Opera which keeps
+/// its single profile at the top level.
+fn chromium_profiles(browser: &'static str, user_data: &Path, found: &mut Vec<Profile>) {
+    let top_level = user_data.join("History");
+    if top_level.is_file() {
+        found.push(Profile {
+            browser: Some(browser),
+            profile: "Default".to_string(),
+            path: top_level,
+        });
+    }
+
+    let Ok(entries) = std::fs::read_dir(user_data) else {
+        return;
+    };
+    let mut profiles: Vec<Profile> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().join("History").is_file())
+        .map(|e| Profile {
+            browser: Some(browser),
+            profile: e.file_name().to_string_lossy().into_owned(),
+            path: e.path().join("History"),
+        })
+        .collect();
+    profiles.sort_by(|a, b| a.profile.cmp(&b.profile));
+    found.append(&mut profiles);
+}
+
+/// Collect `places.sqlite` files from the profiles listed in a Firefox `profiles.ini`.
+fn firefox_profiles(root: &Path, found: &mut Vec<Profile>) {
+    let Ok(ini) = std::fs::read_to_string(root.join("profiles.ini")) else {
+        return;
+    };
+
+    for section in parse_profiles_ini(&ini) {
+        let dir = if section.is_relative {
+            root.join(&section.path)
+        } else {
+            PathBuf::from(&section.path)
+        };
+        let path = dir.join("places.sqlite");
+        if path.is_file() {
+            found.push(Profile {
+                browser: Some("Firefox"),
+                profile: section.name,
+                path,
+            });
+        }
+    }
+}
+
+struct IniProfile {
+    name: String,
+    path: String,
+    is_relative: bool,
+}
+
+/// Read the `[ProfileN]` sections of a Firefox `profiles.ini`.
+fn parse_profiles_ini(ini: &str) -> Vec<IniProfile> {
+    let mut profiles = Vec::new();
+    let mut current: Option<IniProfile> = None;
+
+    for line in ini.lines().map(str::trim) {
+        if line.starts_with('[') {
+            profiles.extend(current.take().filter(|p| !p.path.is_empty()));
+            if line.starts_with("[Profile") {
+                current = Some(IniProfile {
+                    name: String::new(),
+                    path: String::new(),
+                    is_relative: true,
+                });
+            }
+        } else if let (Some(profile), Some((key, value))) = (current.as_mut(), line.split_once('='))
+        {
+            match key.trim() {
+                "Name" => profile.name = value.trim().to_string(),
+                "Path" => profile.path = value.trim().to_string(),
+                "IsRelative" => profile.is_relative = value.trim() == "1",
+                _ => {}
+            }
+        }
+    }
+    profiles.extend(current.filter(|p| !p.path.is_empty()));
+
+    for profile in &mut profiles {
+        if profile.name.is_empty() {
+            profile.name = profile.path.clone();
+        }
+    }
+    profiles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn reads_profile_sections() {
+        let profiles = parse_profiles_ini(
+            "[General]
+             StartWithLastProfile=1
+
+             [Profile1]
+             Name=work
+             IsRelative=1
+             Path=Profiles/abcd.work
+
+             [Install4F96D1932A9F858E]
+             Default=Profiles/abcd.work
+
+             [Profile0]
+             IsRelative=0
+             Path = /srv/firefox/efgh.default
+             Default=1
+
+             [Profile2]
+             Name=no path",
+        );
+        let sections: Vec<(&str, &str, bool)> = profiles
+            .iter()
+            .map(|p| (p.name.as_str(), p.path.as_str(), p.is_relative))
+            .collect();
+        assert_eq!(
+            sections,
+            [
+                ("work", "Profiles/abcd.work", true),
+                // Unnamed profiles are named after their directory
+                (
+                    "/srv/firefox/efgh.default",
+                    "/srv/firefox/efgh.default",
+                    false
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_relative_and_absolute_firefox_profiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("firefox");
+        let elsewhere = dir.path().join("elsewhere");
+        touch(&root.join("abcd.work/places.sqlite"));
+        touch(&elsewhere.join("places.sqlite"));
+        std::fs::write(
+            root.join("profiles.ini"),
+            format!(
+                "[Profile0]\nName=work\nIsRelative=1\nPath=abcd.work\n\n\
+                 [Profile1]\nName=other\nIsRelative=0\nPath={}\n\n\
+                 [Profile2]\nName=gone\nIsRelative=1\nPath=missing\n",
+                elsewhere.display()
+            ),
+        )
+        .unwrap();
+
+        let mut found = Vec::new();
+        firefox_profiles(&root, &mut found);
+        let found: Vec<(&str, &Path)> = found
+            .iter()
+            .map(|p| (p.profile.as_str(), p.path.as_path()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("work", root.join("abcd.work/places.sqlite").as_path()),
+                ("other", elsewhere.join("places.sqlite").as_path()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_native_flatpak_and_snap_profiles() {
+        let home = tempfile::tempdir().unwrap();
+        let home = home.path();
+        for path in [
+            ".config/google-chrome/Default/History",
+            ".config/google-chrome/Profile 1/History",
+            ".config/google-chrome/Crashpad/settings.dat",
+            ".config/opera/History",
+            ".var/app/com.brave.Browser/config/BraveSoftware/Brave-Browser/Default/History",
+            "snap/chromium/common/chromium/Default/History",
+            "snap/firefox/common/.mozilla/firefox/abcd.default/places.sqlite",
+        ] {
+            touch(&home.join(path));
+        }
+        std::fs::write(
+            home.join("snap/firefox/common/.mozilla/firefox/profiles.ini"),
+            "[Profile0]\nName=default\nIsRelative=1\nPath=abcd.default\n",
+        )
+        .unwrap();
+
+        let mut found = Vec::new();
+        linux_profiles(home, &mut found);
+        let found: Vec<(&str, &str)> = found
+            .iter()
+            .map(|p| (p.browser.unwrap(), p.profile.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("Chrome", "Default"),
+                ("Chrome", "Profile 1"),
+                ("Opera", "Default"),
+                ("Brave", "Default"),
+                ("Chromium", "Default"),
+                ("Firefox", "default"),
+            ]
+        );
+    }
+
+    #[test]
+    fn explicit_paths_are_named_after_their_directory() {
+        let profile = Profile::from_path(PathBuf::from("/home/me/.config/chromium/Work/History"));
+        assert_eq!(profile.profile, "Work");
+        assert_eq!(profile.browser, None);
+    }
+}
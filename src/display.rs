@@ -0,0 +1,348 @@
+use crate::history::{TIMELINE_DAYS, TabState, Url};
+use crate::time::Timestamp;
+
+/// Longer URLs are shortened in the middle when shown in the picker.
+const MAX_DISPLAY_URL: usize = 100;
+
+/// How much of a page's stored text the preview shows.
+const MAX_PREVIEW_TEXT: usize = 1000;
+
+/// The text shown for a row in the picker and matched against the query.
+pub fn line(u: &Url) -> String {
+    if u.visit.is_some() {
+        return visit_line(u);
+    }
+    if u.search.is_some() {
+        return search_line(u);
+    }
+    let safe_url = shorten(&u.url, MAX_DISPLAY_URL).replace('\n', " ");
+    let safe_title = u.title.replace('\n', " ");
+    // Tabs are marked as open or closed, bookmarks starred and followed by their folder
+    let folder = match &u.bookmark {
+        Some(folder) => format!(" ({})", folder),
+        None => String::new(),
+    };
+    let marker = match (u.tab, &u.bookmark) {
+        (Some(TabState::Open), _) => "●",
+        (Some(TabState::Closed), _) => "↺",
+        (None, Some(_)) => "★",
+        (None, None) => " ",
+    };
+    format!(
+        "{} {} ... {} [{}]{}",
+        marker,
+        safe_title,
+        safe_url,
+        sources(u),
+        folder
+    )
+}
+
+/// The picker line followed by the page's stored text, for matching page contents as well.
+pub fn line_with_text(u: &Url) -> String {
+    let mut text = line(u);
+    if let Some(page_text) = &u.text {
+        text.push(' ');
+        text.push_str(&page_text.replace('\n', " "));
+    }
+    text
+}
+
+/// A visit-log row: when, how, what, and where from.
+fn visit_line(u: &Url) -> String {
+    let safe_url = shorten(&u.url, MAX_DISPLAY_URL).replace('\n', " ");
+    let safe_title = u.title.replace('\n', " ");
+    let (transition, referrer) = match &u.visit {
+        Some(v) => (v.transition.to_string(), v.referrer.as_deref()),
+        None => (String::new(), None),
+    };
+    let from = match referrer {
+        Some(r) => format!(" ← {}", shorten(r, MAX_DISPLAY_URL).replace('\n', " ")),
+        None => String::new(),
+    };
+    format!(
+        "{} {:<8} {} ... {} [{}]{}",
+        u.last_visit_time,
+        transition,
+        safe_title,
+        safe_url,
+        sources(u),
+        from
+    )
+}
+
+/// A past search: when, what, the results page, and where it led.
+fn search_line(u: &Url) -> String {
+    let safe_url = shorten(&u.url, MAX_DISPLAY_URL).replace('\n', " ");
+    let safe_title = u.title.replace('\n', " ");
+    let to = match u.search.as_ref().and_then(|s| s.landing.as_deref()) {
+        Some(landing) => format!(
+            " → {}",
+            shorten(landing, MAX_DISPLAY_URL).replace('\n', " ")
+        ),
+        None => String::new(),
+    };
+    format!(
+        "{} {} ... {} [{}]{}",
+        u.last_visit_time,
+        safe_title,
+        safe_url,
+        sources(u),
+        to
+    )
+}
+
+fn sources(u: &Url) -> String {
+    u.sources
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// The preview pane for a row: full URL, counts, first and last visit, recent daily visits and
+/// the start of any stored page text.
+pub fn preview(u: &Url, now: Timestamp) -> String {
+    let mut out = String::new();
+    out.push_str(&format!("{}\n\n{}\n\n", u.title, u.url));
+    out.push_str(&format!("Source:      {}\n", sources(u)));
+    if let Some(visit) = &u.visit {
+        out.push_str(&format!("Visited:     {}\n", u.last_visit_time));
+        out.push_str(&format!("Transition:  {}\n", visit.transition));
+        if let Some(referrer) = &visit.referrer {
+            out.push_str(&format!("From:        {}\n", referrer));
+        }
+        return out;
+    }
+    if let Some(search) = &u.search {
+        out.push_str(&format!("Searched:    {}\n", u.last_visit_time));
+        out.push_str(&format!("Searches:    {}\n", u.visit_count));
+        if let Some(landing) = &search.landing {
+            out.push_str(&format!("Led to:      {}\n", landing));
+        }
+        return out;
+    }
+    match u.tab {
+        Some(TabState::Open) => out.push_str("Tab:         open\n"),
+        Some(TabState::Closed) => out.push_str("Tab:         closed recently\n"),
+        None => {}
+    }
+    if let Some(folder) = &u.bookmark {
+        out.push_str(&format!("Bookmarked:  {}\n", folder));
+    }
+    out.push_str(&format!(
+        "Visits:      {} ({} typed)\n",
+        u.visit_count, u.typed_count
+    ));
+    out.push_str(&format!(
+        "First visit: {}\n",
+        u.timeline.first_visit_time.unwrap_or_default()
+    ));
+    out.push_str(&format!("Last visit:  {}\n", u.last_visit_time));
+
+    let today = now.day();
+    let days: Vec<u32> = (today - TIMELINE_DAYS + 1..=today)
+        .map(|day| u.timeline.daily_visits.get(&day).copied().unwrap_or(0))
+        .collect();
+    out.push_str(&format!(
+        "\nLast {} days:\n{}\n",
+        TIMELINE_DAYS,
+        sparkline(&days)
+    ));
+    if let Some(text) = &u.text {
+        out.push_str(&format!("\n{}\n", shorten_end(text, MAX_PREVIEW_TEXT)));
+    }
+    out
+}
+
+/// One bar per value, scaled to the largest; zero is shown as a dot.
+fn sparkline(values: &[u32]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().copied().max().unwrap_or(0).max(1);
+    values
+        .iter()
+        .map(|&v| {
+            if v == 0 {
+                '·'
+            } else {
+                BARS[((v - 1) as usize * BARS.len()) / max as usize]
+            }
+        })
+        .collect()
+}
+
+/// Fill a `--format` template such as `{title}\t{url}` with the fields of a row.
+///
+/// Known fields are `{url}`, `{title}`, `{source}`, `{folder}`, `{visit_count}`,
+/// `{typed_count}`, `{last_visit_time}`, `{tab}` and `{line}`, for visits `{transition}` and
+/// `{referrer}`, and for searches `{landing}`; anything else is left as it is.
+pub fn render(template: &str, u: &Url) -> String {
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(len) = rest[start..].find('}') else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let field = &rest[start + 1..start + len];
+        match field_value(field, u) {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..=start + len]),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+fn field_value(field: &str, u: &Url) -> Option<String> {
+    let value = match field {
+        "url" => u.url.clone(),
+        "title" => u.title.replace('\n', " "),
+        "source" => sources(u),
+        "folder" => u.bookmark.clone().unwrap_or_default(),
+        "visit_count" => u.visit_count.to_string(),
+        "typed_count" => u.typed_count.to_string(),
+        "last_visit_time" => u.last_visit_time.to_string(),
+        "line" => line(u),
+        "tab" => u.tab.map(|t| t.to_string()).unwrap_or_default(),
+        "transition" => u
+            .visit
+            .as_ref()
+            .map(|v| v.transition.to_string())
+            .unwrap_or_default(),
+        "referrer" => u
+            .visit
+            .as_ref()
+            .and_then(|v| v.referrer.clone())
+            .unwrap_or_default(),
+        "landing" => u
+            .search
+            .as_ref()
+            .and_then(|s| s.landing.clone())
+            .unwrap_or_default(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Shorten `text` to at most `width` characters by cutting off the end.
+fn shorten_end(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let start: String = text.chars().take(width.saturating_sub(1)).collect();
+    format!("{}…", start)
+}
+
+/// Shorten `text` to at most `width` characters by cutting out the middle.
+pub fn shorten(text: &str, width: usize) -> String {
+    let len = text.chars().count();
+    if len <= width || width < 2 {
+        return text.to_string();
+    }
+    let head = (width - 1) / 2;
+    let tail = width - 1 - head;
+    let start: String = text.chars().take(head).collect();
+    let end: String = text.chars().skip(len - tail).collect();
+    format!("{}…{}", start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{SearchDetail, Source, Transition, VisitDetail};
+
+    fn page() -> Url {
+        Url {
+            url: "https://a.example/".to_string(),
+            title: "A\npage".to_string(),
+            visit_count: 3,
+            sources: vec![Source {
+                browser: "Firefox".to_string(),
+                profile: "work".to_string(),
+                path: "/p/places.sqlite".into(),
+            }],
+            ..Url::default()
+        }
+    }
+
+    #[test]
+    fn renders_known_fields_and_keeps_the_rest() {
+        let u = page();
+        assert_eq!(
+            render("{title}\t{url} {visit_count} {source} {nope} {", &u),
+            "A page\thttps://a.example/ 3 Firefox:work {nope} {"
+        );
+        // Fields that don't apply to the row are empty
+        assert_eq!(render("[{tab}][{landing}][{folder}]", &u), "[][][]");
+
+        let tab = Url {
+            tab: Some(TabState::Closed),
+            ..page()
+        };
+        assert_eq!(render("{tab}", &tab), "closed");
+
+        let search = Url {
+            search: Some(SearchDetail {
+                term: "rust".to_string(),
+                landing: Some("https://rust-lang.org/".to_string()),
+            }),
+            ..page()
+        };
+        assert_eq!(render("{landing}", &search), "https://rust-lang.org/");
+    }
+
+    #[test]
+    fn marks_tabs_and_bookmarks() {
+        let marker = |tab: Option<TabState>, bookmark: Option<&str>| {
+            let u = Url {
+                tab,
+                bookmark: bookmark.map(str::to_string),
+                ..page()
+            };
+            line(&u)
+        };
+        assert_eq!(
+            marker(None, None),
+            "  A page ... https://a.example/ [Firefox:work]"
+        );
+        assert_eq!(
+            marker(None, Some("Work/Docs")),
+            "★ A page ... https://a.example/ [Firefox:work] (Work/Docs)"
+        );
+        assert!(marker(Some(TabState::Open), Some("Work")).starts_with("● A page"));
+        assert!(marker(Some(TabState::Closed), None).starts_with("↺ A page"));
+    }
+
+    #[test]
+    fn visit_and_search_lines_show_where_they_lead() {
+        let visit = Url {
+            visit: Some(VisitDetail {
+                transition: Transition::Link,
+                referrer: Some("https://b.example/".to_string()),
+            }),
+            ..page()
+        };
+        assert!(line(&visit).ends_with(
+            "link     A page ... https://a.example/ [Firefox:work] ← https://b.example/"
+        ));
+
+        let search = Url {
+            search: Some(SearchDetail {
+                term: "rust".to_string(),
+                landing: Some("https://rust-lang.org/".to_string()),
+            }),
+            ..page()
+        };
+        assert!(line(&search).ends_with("[Firefox:work] → https://rust-lang.org/"));
+    }
+
+    #[test]
+    fn shortens_long_text() {
+        assert_eq!(shorten("abcdefghij", 5), "ab…ij");
+        assert_eq!(shorten("abc", 5), "abc");
+        assert_eq!(shorten_end("abcdefghij", 5), "abcd…");
+        assert_eq!(sparkline(&[0, 1, 4, 8]), "·▁▄█");
+    }
+}
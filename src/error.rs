@@ -0,0 +1,184 @@
+use std::path::PathBuf;
+
+/// Exit status when nothing matched the query and filters.
+pub const EXIT_NO_MATCHES: i32 = 1;
+/// Exit status for any error.
+pub const EXIT_ERROR: i32 = 2;
+/// Exit status when the picker was closed without choosing anything, as fzf does.
+pub const EXIT_NO_SELECTION: i32 = 130;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The config file could not be read or parsed.
+    Config {
+        path: PathBuf,
+        message: String,
+    },
+    /// No history database was configured or discovered.
+    NoSources,
+    SourceNotFound(PathBuf),
+    /// The database could not be copied into a private snapshot.
+    Copy {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The database has none of the tables of a supported browser.
+    SchemaUnsupported(PathBuf),
+    Sqlite {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+    /// fuhl's own files, such as the index, could not be created or read.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A Chrome `Bookmarks` file could not be parsed.
+    Bookmarks {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    /// A browser's session file, listing its tabs, could not be understood.
+    Session {
+        path: PathBuf,
+        message: String,
+    },
+    /// A page could not be downloaded for its text.
+    Fetch {
+        url: String,
+        message: String,
+    },
+    /// A full-text query could not be run.
+    Search(rusqlite::Error),
+    /// A URL could not be opened in the default browser or with the `--open-with` command.
+    Open {
+        url: String,
+        source: std::io::Error,
+    },
+    /// A new or private window was asked for in a browser fuhl cannot start, so the default
+    /// browser was used instead.
+    NoWindow {
+        browser: String,
+    },
+    /// A browser could not be started.
+    Launch {
+        program: String,
+        source: std::io::Error,
+    },
+    /// The selection could not be copied to the clipboard.
+    Clipboard(std::io::Error),
+    Skim(String),
+    /// A failure in a [`crate::HistorySource`] outside this crate.
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+    NoMatches,
+    NoSelection,
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::NoMatches => EXIT_NO_MATCHES,
+            Error::NoSelection => EXIT_NO_SELECTION,
+            _ => EXIT_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Config { path, message } => {
+                write!(f, "Invalid config file {}: {}", path.display(), message)
+            }
+            Error::NoSources => write!(
+                f,
+                "No browser history found; set FUHL_DB, --db or `sources` in the config file to a \
+                 History, places.sqlite or History.db file"
+            ),
+            Error::SourceNotFound(path) => write!(
+                f,
+                "History DB not found at path {}; check FUHL_DB, --db and `sources` in the config file",
+                path.display()
+            ),
+            Error::Copy { path, source } => {
+                write!(f, "Failed to copy {}: {}", path.display(), source)?;
+                if source.kind() == std::io::ErrorKind::PermissionDenied {
+                    if cfg!(target_os = "macos") {
+                        write!(
+                            f,
+                            "; give your terminal Full Disk Access in System Settings > Privacy & Security"
+                        )?;
+                    } else {
+                        write!(f, "; check that the file is readable by the current user")?;
+                    }
+                }
+                Ok(())
+            }
+            Error::SchemaUnsupported(path) => write!(
+                f,
+                "Unrecognised history DB schema at path {}; expected a Chrome History, Firefox \
+                 places.sqlite or Safari History.db file",
+                path.display()
+            ),
+            Error::Sqlite { path, source } => {
+                write!(f, "Failed to read {}: {}", path.display(), source)?;
+                if matches!(
+                    source.sqlite_error_code(),
+                    Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+                ) {
+                    write!(f, "; close the browser or try again")?;
+                }
+                Ok(())
+            }
+            Error::Io { path, source } => {
+                write!(f, "Failed to access {}: {}", path.display(), source)
+            }
+            Error::Bookmarks { path, source } => {
+                write!(f, "Failed to read bookmarks {}: {}", path.display(), source)
+            }
+            Error::Session { path, message } => {
+                write!(
+                    f,
+                    "Failed to read tabs from {}: {}",
+                    path.display(),
+                    message
+                )
+            }
+            Error::Fetch { url, message } => write!(f, "Failed to fetch {}: {}", url, message),
+            Error::Search(source) => write!(f, "Full-text search failed: {}", source),
+            Error::Open { url, source } => write!(f, "Failed to open URL {}: {}", url, source),
+            Error::NoWindow { browser } => write!(
+                f,
+                "Failed to open a window in {}, using the default browser",
+                browser
+            ),
+            Error::Launch { program, source } => {
+                write!(f, "Failed to start {}: {}", program, source)
+            }
+            Error::Clipboard(source) => write!(f, "Failed to copy to the clipboard: {}", source),
+            Error::Skim(message) => write!(f, "Picker failed: {}", message),
+            Error::Custom(e) => write!(f, "{}", e),
+            Error::NoMatches => write!(f, "No URLs found"),
+            Error::NoSelection => write!(f, "No selection made"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Copy { source, .. } => Some(source),
+            Error::Sqlite { source, .. } => Some(source),
+            Error::Io { source, .. } => Some(source),
+            Error::Bookmarks { source, .. } => Some(source),
+            Error::Search(source) => Some(source),
+            Error::Open { source, .. } => Some(source),
+            Error::Launch { source, .. } => Some(source),
+            Error::Clipboard(source) => Some(source),
+            Error::Custom(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::history::Url;
+use crate::time::Timestamp;
+use regex::Regex;
+
+/// Browser-internal and local schemes that are dropped unless allowed explicitly.
+pub const DEFAULT_EXCLUDED_SCHEMES: &[&str] = &[
+    "about",
+    "brave",
+    "chrome",
+    "chrome-extension",
+    "chrome-search",
+    "devtools",
+    "edge",
+    "file",
+    "moz-extension",
+    "opera",
+    "vivaldi",
+];
+
+/// Which rows are offered in the picker.
+#[derive(Debug)]
+pub struct Filter {
+    /// Drop URLs longer than this many characters.
+    pub max_length: Option<usize>,
+    /// When non-empty, keep only rows whose URL matches one of these.
+    pub include_url: Vec<Regex>,
+    /// When non-empty, keep only rows whose title matches one of these.
+    pub include_title: Vec<Regex>,
+    pub exclude_url: Vec<Regex>,
+    pub exclude_title: Vec<Regex>,
+    /// Keep rows the browser marked hidden (subframes, redirects and the like).
+    pub show_hidden: bool,
+    /// Lower-case schemes, without `://`, to drop.
+    pub excluded_schemes: Vec<String>,
+    /// Keep only rows last visited at or after this time.
+    pub since: Option<Timestamp>,
+    /// Keep only rows last visited before this time.
+    pub before: Option<Timestamp>,
+    /// Keep only pages open in a tab, or in one closed recently.
+    pub tabs: bool,
+}
+
+impl Filter {
+    pub fn matches(&self, u: &Url) -> bool {
+        if u.hidden != 0 && !self.show_hidden {
+            return false;
+        }
+        if self.tabs && u.tab.is_none() {
+            return false;
+        }
+        if self.since.is_some_and(|t| u.last_visit_time < t)
+            || self.before.is_some_and(|t| u.last_visit_time >= t)
+        {
+            return false;
+        }
+        if self
+            .max_length
+            .is_some_and(|max| u.url.chars().count() > max)
+        {
+            return false;
+        }
+        if let Some((scheme, _)) = u.url.split_once(':')
+            && self
+                .excluded_schemes
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(scheme))
+        {
+            return false;
+        }
+        if !self.include_url.is_empty() && !self.include_url.iter().any(|r| r.is_match(&u.url)) {
+            return false;
+        }
+        if !self.include_title.is_empty()
+            && !self.include_title.iter().any(|r| r.is_match(&u.title))
+        {
+            return false;
+        }
+        !self.exclude_url.iter().any(|r| r.is_match(&u.url))
+            && !self.exclude_title.iter().any(|r| r.is_match(&u.title))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::TabState;
+
+    fn filter() -> Filter {
+        Filter {
+            max_length: None,
+            include_url: Vec::new(),
+            include_title: Vec::new(),
+            exclude_url: Vec::new(),
+            exclude_title: Vec::new(),
+            show_hidden: false,
+            excluded_schemes: DEFAULT_EXCLUDED_SCHEMES
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            since: None,
+            before: None,
+            tabs: false,
+        }
+    }
+
+    fn visited(url: &str, micros: i64) -> Url {
+        Url {
+            url: url.to_string(),
+            title: "Page".to_string(),
+            last_visit_time: Timestamp::from_unix_micros(micros),
+            ..Url::default()
+        }
+    }
+
+    #[test]
+    fn keeps_visits_from_since_up_to_before() {
+        let filter = Filter {
+            since: Some(Timestamp::from_unix_micros(100)),
+            before: Some(Timestamp::from_unix_micros(200)),
+            ..filter()
+        };
+        let kept: Vec<i64> = [99, 100, 199, 200]
+            .into_iter()
+            .filter(|&t| filter.matches(&visited("https://a.example/", t)))
+            .collect();
+        assert_eq!(kept, [100, 199]);
+    }
+
+    #[test]
+    fn keeps_only_tabs_when_asked() {
+        let filter = Filter {
+            tabs: true,
+            ..filter()
+        };
+        let page = visited("https://a.example/", 1);
+        assert!(!filter.matches(&page));
+        assert!(filter.matches(&Url {
+            tab: Some(TabState::Closed),
+            ..page
+        }));
+    }
+
+    #[test]
+    fn drops_internal_hidden_long_and_excluded_urls() {
+        let filter = Filter {
+            max_length: Some(30),
+            include_url: vec![Regex::new("example").unwrap()],
+            exclude_title: vec![Regex::new("(?i)login").unwrap()],
+            ..filter()
+        };
+        assert!(filter.matches(&visited("https://a.example/", 1)));
+        assert!(!filter.matches(&visited("CHROME://a.example/", 1)));
+        assert!(!filter.matches(&visited("https://a.example/a/very/long/path", 1)));
+        assert!(!filter.matches(&visited("https://other.test/", 1)));
+        assert!(!filter.matches(&Url {
+            hidden: 1,
+            ..visited("https://a.example/", 1)
+        }));
+        assert!(!filter.matches(&Url {
+            title: "Login".to_string(),
+            ..visited("https://a.example/", 1)
+        }));
+    }
+}
@@ -0,0 +1,364 @@
+use crate::history::{
+    MAX_VISIT_SAMPLES, TIMELINE_DAYS, TabState, Timeline, Transition, Url, Visit, VisitDetail,
+    attach_timelines, attach_visits, collect_rows, timelines,
+};
+use crate::time::{MICROS_PER_DAY, Timestamp, UNIX_EPOCH_OFFSET_MICROS};
+use rusqlite::Connection;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// `moz_historyvisits.visit_type` for a URL typed into the address bar.
+const TRANSITION_TYPED: i64 = 2;
+
+/// Read `moz_places` and its visits from a Firefox `places.sqlite` database.
+pub fn read_urls(
+    conn: &Connection,
+    since: Timestamp,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.url, COALESCE(p.title, ''), p.visit_count,
+                COUNT(CASE WHEN v.visit_type = ?1 THEN 1 END), MAX(v.visit_date), p.hidden
+         FROM moz_places p
+         JOIN moz_historyvisits v ON v.place_id = p.id
+         GROUP BY p.id
+         HAVING MAX(v.visit_date) >= ?2
+         ORDER BY MAX(v.visit_date) DESC, p.visit_count DESC",
+    )?;
+
+    let url_iter = stmt.query_map([TRANSITION_TYPED, since.unix_micros()], |row| {
+        Ok(Url {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            title: row.get(2)?,
+            visit_count: row.get(3)?,
+            typed_count: row.get(4)?,
+            last_visit_time: Timestamp::from_unix_micros(row.get(5)?),
+            hidden: row.get(6)?,
+            sources: Vec::new(),
+            bookmark: None,
+            visits: Vec::new(),
+            timeline: Timeline::default(),
+            visit: None,
+            text: None,
+            search: None,
+            tab: None,
+        })
+    })?;
+
+    let mut urls = collect_rows(url_iter, skipped);
+    attach_visits(&mut urls, read_visits(conn, since)?);
+    attach_timelines(&mut urls, read_timelines(conn, since)?);
+    Ok(urls)
+}
+
+/// Read the description of every page in `moz_places` that has one, taken from the page's
+/// `description` meta tags when it was visited.
+pub fn read_descriptions(
+    conn: &Connection,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, url, COALESCE(title, ''), description
+         FROM moz_places
+         WHERE description IS NOT NULL AND TRIM(description) != ''",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Url {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            title: row.get(2)?,
+            text: Some(row.get(3)?),
+            ..Url::default()
+        })
+    })?;
+    Ok(collect_rows(rows, skipped))
+}
+
+/// Read every row of `moz_historyvisits`, newest first, with the place it came from.
+pub fn read_visit_log(
+    conn: &Connection,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.url, COALESCE(p.title, ''), v.visit_date, v.visit_type, p.hidden, r.url
+         FROM moz_historyvisits v
+         JOIN moz_places p ON p.id = v.place_id
+         LEFT JOIN moz_historyvisits f ON f.id = v.from_visit
+         LEFT JOIN moz_places r ON r.id = f.place_id
+         ORDER BY v.visit_date DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok(Url::visit(
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            Timestamp::from_unix_micros(row.get(3)?),
+            row.get(5)?,
+            VisitDetail {
+                transition: transition(row.get(4)?),
+                referrer: row.get(6)?,
+            },
+        ))
+    })?;
+    Ok(collect_rows(rows, skipped))
+}
+
+/// First visit and recent per-day visit counts for every place, from `moz_historyvisits`.
+fn read_timelines(conn: &Connection, since: Timestamp) -> rusqlite::Result<HashMap<i64, Timeline>> {
+    let mut first = conn.prepare(
+        "SELECT place_id, MIN(visit_date) FROM moz_historyvisits
+         WHERE place_id IN (SELECT id FROM moz_places WHERE last_visit_date >= ?1)
+         GROUP BY place_id",
+    )?;
+    let mut daily = conn.prepare(
+        "SELECT place_id, (visit_date + ?1) / ?2 AS day, COUNT(*) FROM moz_historyvisits
+         WHERE visit_date + ?1 >= ?3
+           AND place_id IN (SELECT id FROM moz_places WHERE last_visit_date >= ?4)
+         GROUP BY place_id, day",
+    )?;
+    let start = Timestamp::now().webkit_micros() - TIMELINE_DAYS * MICROS_PER_DAY;
+    timelines(
+        first.query_map([since.unix_micros()], |row| {
+            Ok((row.get(0)?, Timestamp::from_unix_micros(row.get(1)?)))
+        })?,
+        daily.query_map(
+            [
+                UNIX_EPOCH_OFFSET_MICROS,
+                MICROS_PER_DAY,
+                start,
+                since.unix_micros(),
+            ],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?,
+    )
+}
+
+/// Sample the most recent rows of `moz_historyvisits` for every place.
+fn read_visits(conn: &Connection, since: Timestamp) -> rusqlite::Result<HashMap<i64, Vec<Visit>>> {
+    let mut stmt = conn.prepare(
+        "SELECT place_id, visit_date, visit_type FROM (
+             SELECT place_id, visit_date, visit_type,
+                    ROW_NUMBER() OVER (PARTITION BY place_id ORDER BY visit_date DESC) AS n
+             FROM moz_historyvisits
+             WHERE place_id IN (SELECT id FROM moz_places WHERE last_visit_date >= ?2)
+         )
+         WHERE n <= ?1
+         ORDER BY place_id, visit_date DESC",
+    )?;
+    let mut rows = stmt.query([MAX_VISIT_SAMPLES as i64, since.unix_micros()])?;
+
+    let mut visits: HashMap<i64, Vec<Visit>> = HashMap::new();
+    while let Some(row) = rows.next()? {
+        visits.entry(row.get(0)?).or_default().push(Visit {
+            time: Timestamp::from_unix_micros(row.get(1)?),
+            transition: transition(row.get(2)?),
+        });
+    }
+    Ok(visits)
+}
+
+/// Map a `moz_historyvisits.visit_type` value onto [`Transition`].
+pub fn transition(visit_type: i64) -> Transition {
+    match visit_type {
+        1 => Transition::Link,
+        TRANSITION_TYPED => Transition::Typed,
+        3 => Transition::Bookmark,
+        5 | 6 => Transition::Redirect,
+        9 => Transition::Reload,
+        _ => Transition::Other,
+    }
+}
+
+/// Read `moz_bookmarks` with the folder path of each bookmark.
+pub fn read_bookmarks(
+    conn: &Connection,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    // moz_bookmarks.type is 1 for bookmarks and 2 for folders; the root folder has parent 0
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE folders(id, path) AS (
+             SELECT id, '' FROM moz_bookmarks WHERE parent = 0
+             UNION ALL
+             SELECT b.id, CASE WHEN f.path = '' THEN COALESCE(b.title, '')
+                               ELSE f.path || '/' || COALESCE(b.title, '') END
+             FROM moz_bookmarks b JOIN folders f ON b.parent = f.id
+             WHERE b.type = 2
+         )
+         SELECT p.id, p.url, COALESCE(b.title, p.title, ''), f.path
+         FROM moz_bookmarks b
+         JOIN moz_places p ON p.id = b.fk
+         JOIN folders f ON f.id = b.parent
+         WHERE b.type = 1",
+    )?;
+
+    let url_iter = stmt.query_map([], |row| {
+        Ok(Url {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            title: row.get(2)?,
+            visit_count: 0,
+            typed_count: 0,
+            last_visit_time: Timestamp::default(),
+            hidden: 0,
+            sources: Vec::new(),
+            bookmark: Some(row.get(3)?),
+            visits: Vec::new(),
+            timeline: Timeline::default(),
+            visit: None,
+            text: None,
+            search: None,
+            tab: None,
+        })
+    })?;
+
+    Ok(collect_rows(url_iter, skipped))
+}
+
+/// Read the open and recently closed tabs of a Firefox session store, the JSON inside
+/// `sessionstore-backups/recovery.jsonlz4`.
+pub fn read_session(session: &Value) -> Vec<Url> {
+    let mut tabs = Vec::new();
+    for window in list(&session["windows"]) {
+        for tab in list(&window["tabs"]) {
+            tabs.extend(session_tab(tab, &tab["lastAccessed"], TabState::Open));
+        }
+        for closed in list(&window["_closedTabs"]) {
+            tabs.extend(session_tab(
+                &closed["state"],
+                &closed["closedAt"],
+                TabState::Closed,
+            ));
+        }
+    }
+    for window in list(&session["_closedWindows"]) {
+        for tab in list(&window["tabs"]) {
+            tabs.extend(session_tab(tab, &window["closedAt"], TabState::Closed));
+        }
+        for closed in list(&window["_closedTabs"]) {
+            tabs.extend(session_tab(
+                &closed["state"],
+                &closed["closedAt"],
+                TabState::Closed,
+            ));
+        }
+    }
+    tabs
+}
+
+fn list(value: &Value) -> &[Value] {
+    value.as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// The page a tab shows: the entry its 1-based `index` points to, or else its last.
+fn session_tab(tab: &Value, millis: &Value, state: TabState) -> Option<Url> {
+    let entries = list(&tab["entries"]);
+    let entry = tab["index"]
+        .as_u64()
+        .and_then(|i| entries.get((i as usize).checked_sub(1)?))
+        .or(entries.last())?;
+    let time = millis
+        .as_i64()
+        .map(|ms| Timestamp::from_unix_micros(ms * 1000))
+        .unwrap_or_default();
+    Some(Url::tab(
+        entry["url"].as_str()?.to_string(),
+        entry["title"].as_str().unwrap_or_default().to_string(),
+        time,
+        state,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Browser;
+
+    #[test]
+    fn reads_descriptions_where_firefox_keeps_them() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url TEXT, title TEXT);
+             INSERT INTO moz_places VALUES (1, 'https://a.example/', 'A');",
+        )
+        .unwrap();
+        // Firefox before 63 has no descriptions
+        let read = |conn: &Connection| {
+            Browser::Firefox
+                .read_descriptions(conn, &mut Vec::new())
+                .unwrap()
+        };
+        assert!(read(&conn).is_empty());
+
+        conn.execute_batch(
+            "ALTER TABLE moz_places ADD COLUMN description TEXT;
+             UPDATE moz_places SET description = 'Runbook for payments' WHERE id = 1;
+             INSERT INTO moz_places VALUES (2, 'https://b.example/', NULL, '  ');
+             INSERT INTO moz_places VALUES (3, 'https://c.example/', NULL, NULL);",
+        )
+        .unwrap();
+        let pages: Vec<(String, Option<String>)> =
+            read(&conn).into_iter().map(|u| (u.url, u.text)).collect();
+        assert_eq!(
+            pages,
+            [(
+                "https://a.example/".to_string(),
+                Some("Runbook for payments".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn reads_open_and_closed_tabs() {
+        let session: Value = serde_json::from_str(
+            r#"{
+                "windows": [{
+                    "tabs": [
+                        {"entries": [{"url": "https://a.example/", "title": "A"},
+                                     {"url": "https://b.example/", "title": "B"}],
+                         "index": 1, "lastAccessed": 1700000000000},
+                        {"entries": [{"url": "about:blank"}]}
+                    ],
+                    "_closedTabs": [
+                        {"state": {"entries": [{"url": "https://c.example/", "title": "C"}],
+                                   "index": 1},
+                         "closedAt": 1700000001000}
+                    ]
+                }],
+                "_closedWindows": [{
+                    "tabs": [{"entries": [{"url": "https://d.example/"}], "index": 1}],
+                    "closedAt": 1700000002000
+                }]
+            }"#,
+        )
+        .unwrap();
+        let tabs: Vec<(String, Option<TabState>, Option<i64>)> = read_session(&session)
+            .into_iter()
+            .map(|u| {
+                let time = u.last_visit_time;
+                (u.url, u.tab, (!time.is_never()).then(|| time.unix_micros()))
+            })
+            .collect();
+        assert_eq!(
+            tabs,
+            [
+                (
+                    "https://a.example/".to_string(),
+                    Some(TabState::Open),
+                    Some(1_700_000_000_000_000)
+                ),
+                ("about:blank".to_string(), Some(TabState::Open), None),
+                (
+                    "https://c.example/".to_string(),
+                    Some(TabState::Closed),
+                    Some(1_700_000_001_000_000)
+                ),
+                (
+                    "https://d.example/".to_string(),
+                    Some(TabState::Closed),
+                    Some(1_700_000_002_000_000)
+                ),
+            ]
+        );
+    }
+}
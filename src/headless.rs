@@ -0,0 +1,127 @@
+use crate::display;
+use crate::history::Url;
+use skim::prelude::*;
+use skim::{MatchEngine, Rank};
+
+/// Rows whose picker line matches `query` the way skim would match it, in ranked order.
+pub fn matches<'a>(urls: &'a [Url], query: &str) -> Vec<&'a Url> {
+    if query.trim().is_empty() {
+        return urls.iter().collect();
+    }
+
+    let engine =
+        AndOrEngineFactory::new(ExactOrFuzzyEngineFactory::builder().build()).create_engine(query);
+    urls.iter()
+        .filter(|u| {
+            let item: Arc<dyn SkimItem> = Arc::new(display::line(u));
+            engine.match_item(item).is_some()
+        })
+        .collect()
+}
+
+/// Rows whose picker line or stored page text matches `query`, in the order the picker shows
+/// them: best match first, then in ranked order, see [`PageTextMatcher`].
+pub fn matches_page_text<'a>(urls: &'a [Url], query: &str) -> Vec<&'a Url> {
+    if query.trim().is_empty() {
+        return urls.iter().collect();
+    }
+
+    let matcher = PageTextMatcher::new(query);
+    let mut found: Vec<(Rank, usize)> = urls
+        .iter()
+        .enumerate()
+        .filter_map(|(index, u)| Some((matcher.match_url(u)?.0, index)))
+        .collect();
+    found.sort();
+    found.into_iter().map(|(_, index)| &urls[index]).collect()
+}
+
+/// Matches a query the way the picker does with page text: fuzzily against the line shown, as
+/// without page text, or else exactly against the line followed by the text. Scattered letters
+/// are found in almost any page, so the text is never matched fuzzily.
+pub(crate) struct PageTextMatcher {
+    fuzzy: Box<dyn MatchEngine>,
+    exact: Box<dyn MatchEngine>,
+}
+
+impl PageTextMatcher {
+    pub(crate) fn new(query: &str) -> PageTextMatcher {
+        let fuzzy = AndOrEngineFactory::new(ExactOrFuzzyEngineFactory::builder().build())
+            .create_engine(query);
+        let exact = AndOrEngineFactory::new(
+            ExactOrFuzzyEngineFactory::builder()
+                .exact_mode(true)
+                .build(),
+        )
+        .create_engine(query);
+        PageTextMatcher { fuzzy, exact }
+    }
+
+    /// How well `u` matches, lower first as in skim, and which characters of its line matched.
+    /// Matches in the text alone rank after every match in the line.
+    pub(crate) fn match_url(&self, u: &Url) -> Option<(Rank, Vec<usize>)> {
+        let line = display::line(u);
+        let item: Arc<dyn SkimItem> = Arc::new(line.clone());
+        if let Some(m) = self.fuzzy.match_item(item) {
+            return Some((m.rank, m.range_char_indices(&line)));
+        }
+        u.text.as_ref()?;
+        let with_text = display::line_with_text(u);
+        let item: Arc<dyn SkimItem> = Arc::new(with_text.clone());
+        let m = self.exact.match_item(item)?;
+        let shown = line.chars().count();
+        let highlights = m
+            .range_char_indices(&with_text)
+            .into_iter()
+            .filter(|&i| i < shown)
+            .collect();
+        Some(([i32::MAX; 5], highlights))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_text_is_matched_exactly_and_lines_fuzzily() {
+        let page = |url: &str, title: &str, text: Option<&str>| Url {
+            url: url.to_string(),
+            title: title.to_string(),
+            text: text.map(str::to_string),
+            ..Url::default()
+        };
+        let urls = vec![
+            page(
+                "https://grafana.example/d/1",
+                "Dashboard",
+                Some("capacity planning"),
+            ),
+            page("https://wiki.example/cp", "Capacity plan", None),
+            page("https://other.example/", "Other", Some("zebra quokka")),
+        ];
+        let found = |query: &str| -> Vec<&str> {
+            matches_page_text(&urls, query)
+                .iter()
+                .map(|u| u.url.as_str())
+                .collect()
+        };
+
+        // Matches in the line come before matches in the text alone, as in the picker
+        assert_eq!(
+            found("capacity"),
+            ["https://wiki.example/cp", "https://grafana.example/d/1"]
+        );
+        // Fuzzy on the line still works: "grfna" is scattered over "grafana"
+        assert_eq!(found("grfna"), ["https://grafana.example/d/1"]);
+        // Scattered letters in the text are not a match
+        assert!(found("zbrqk").is_empty());
+        assert_eq!(found("grafana capacity"), ["https://grafana.example/d/1"]);
+
+        let matcher = PageTextMatcher::new("capacity");
+        let (line_rank, _) = matcher.match_url(&urls[1]).unwrap();
+        let (text_rank, highlights) = matcher.match_url(&urls[0]).unwrap();
+        assert!(line_rank < text_rank);
+        assert!(highlights.is_empty());
+    }
+}
@@ -0,0 +1,561 @@
+use crate::error::{Error, Result};
+use crate::time::Timestamp;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// How many of the most recent visits are kept per URL for ranking.
+pub const MAX_VISIT_SAMPLES: usize = 10;
+
+/// How many days of per-day visit counts are kept per URL for the preview.
+pub const TIMELINE_DAYS: i64 = 30;
+
+#[derive(Debug, Default)]
+pub struct Url {
+    pub id: i64,
+    pub url: String,
+    pub title: String,
+    pub visit_count: i64,
+    pub typed_count: i64,
+    /// Never for bookmarks that have not been visited.
+    pub last_visit_time: Timestamp,
+    pub hidden: i64,
+    /// Where the row was read from; after [`merge`] the most recently visited source comes first.
+    pub sources: Vec<Source>,
+    /// Folder path of the bookmark for this URL, if it is bookmarked.
+    pub bookmark: Option<String>,
+    /// The most recent visits, newest first, up to [`MAX_VISIT_SAMPLES`].
+    pub visits: Vec<Visit>,
+    pub timeline: Timeline,
+    /// Set when the row stands for a single visit rather than a page; see
+    /// [`Browser::read_visit_log`]. The visit time is then `last_visit_time`.
+    pub visit: Option<VisitDetail>,
+    /// Readable text of the page, when some has been stored; see [`crate::pages`].
+    pub text: Option<String>,
+    /// Set when the row stands for a past search rather than a page; see
+    /// [`Browser::read_searches`]. `url` is then the results page, so opening it searches again,
+    /// and `title` the search as it was typed.
+    pub search: Option<SearchDetail>,
+    /// Set when the page is open in a tab, or was in one closed recently; see [`crate::tabs`].
+    pub tab: Option<TabState>,
+}
+
+impl Url {
+    /// A row of the visit log: one visit to `url` at `time`.
+    pub fn visit(
+        id: i64,
+        url: String,
+        title: String,
+        time: Timestamp,
+        hidden: i64,
+        detail: VisitDetail,
+    ) -> Url {
+        Url {
+            id,
+            url,
+            title,
+            visit_count: 1,
+            typed_count: (detail.transition == Transition::Typed) as i64,
+            last_visit_time: time,
+            hidden,
+            sources: Vec::new(),
+            bookmark: None,
+            visits: Vec::new(),
+            timeline: Timeline::default(),
+            visit: Some(detail),
+            text: None,
+            search: None,
+            tab: None,
+        }
+    }
+
+    /// A page open in a tab, or in one that was closed, last seen at `time`.
+    pub fn tab(url: String, title: String, time: Timestamp, state: TabState) -> Url {
+        Url {
+            url,
+            title,
+            last_visit_time: time,
+            tab: Some(state),
+            ..Url::default()
+        }
+    }
+}
+
+/// When a URL was visited, beyond the last visit.
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    pub first_visit_time: Option<Timestamp>,
+    /// Visits per day over the last [`TIMELINE_DAYS`] days, keyed by [`Timestamp::day`].
+    pub daily_visits: BTreeMap<i64, u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Visit {
+    pub time: Timestamp,
+    pub transition: Transition,
+}
+
+/// How a single visit in the visit log came about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VisitDetail {
+    pub transition: Transition,
+    /// The page the visit was reached from, if the browser recorded one.
+    pub referrer: Option<String>,
+}
+
+/// What a past search was for and where it led.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchDetail {
+    /// The search as the browser normalised it, which tells repeats apart from new searches.
+    pub term: String,
+    /// The page most recently reached from the search results, if any.
+    pub landing: Option<String>,
+}
+
+/// Whether a page is open in a browser tab or was in one that was closed recently.
+///
+/// Ordered so that the larger state wins when rows are merged: a page that is open and was also
+/// closed elsewhere is open.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TabState {
+    Closed,
+    Open,
+}
+
+impl std::fmt::Display for TabState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            TabState::Closed => "closed",
+            TabState::Open => "open",
+        })
+    }
+}
+
+/// How the browser got to a page, reduced to the kinds that matter for ranking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Transition {
+    Link,
+    Typed,
+    Bookmark,
+    Redirect,
+    Reload,
+    Other,
+}
+
+impl std::fmt::Display for Transition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Transition::Link => "link",
+            Transition::Typed => "typed",
+            Transition::Bookmark => "bookmark",
+            Transition::Redirect => "redirect",
+            Transition::Reload => "reload",
+            Transition::Other => "other",
+        };
+        f.pad(name)
+    }
+}
+
+/// The browser and profile a history row came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Source {
+    pub browser: String,
+    pub profile: String,
+    /// The history database the row was read from, inside the profile's directory.
+    pub path: PathBuf,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.profile.is_empty() {
+            write!(f, "{}", self.browser)
+        } else {
+            write!(f, "{}:{}", self.browser, self.profile)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    Chrome,
+    Firefox,
+    Safari,
+}
+
+impl Browser {
+    /// Work out which browser wrote the history database by looking at its tables.
+    pub fn detect(conn: &Connection) -> Option<Browser> {
+        if has_table(conn, "moz_places") {
+            Some(Browser::Firefox)
+        } else if has_table(conn, "history_items") {
+            Some(Browser::Safari)
+        } else if has_table(conn, "urls") {
+            Some(Browser::Chrome)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Browser::Chrome => "Chrome",
+            Browser::Firefox => "Firefox",
+            Browser::Safari => "Safari",
+        }
+    }
+
+    /// Read the pages last visited at or after `since`; the default timestamp reads them all.
+    ///
+    /// Rows that cannot be decoded are left out, and their errors added to `skipped`; the same
+    /// goes for the other readers.
+    pub fn read_urls(
+        self,
+        conn: &Connection,
+        since: Timestamp,
+        skipped: &mut Vec<rusqlite::Error>,
+    ) -> rusqlite::Result<Vec<Url>> {
+        match self {
+            Browser::Chrome => crate::chrome::read_urls(conn, since, skipped),
+            Browser::Firefox => crate::firefox::read_urls(conn, since, skipped),
+            Browser::Safari => crate::safari::read_urls(conn, since, skipped),
+        }
+    }
+
+    /// The URL of every page still in the browser's history, however long ago it was visited.
+    pub fn read_page_urls(self, conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+        let sql = match self {
+            Browser::Chrome => "SELECT url FROM urls",
+            Browser::Firefox => {
+                "SELECT url FROM moz_places WHERE id IN (SELECT place_id FROM moz_historyvisits)"
+            }
+            Browser::Safari => "SELECT url FROM history_items",
+        };
+        let mut stmt = conn.prepare(sql)?;
+        let urls = stmt.query_map([], |row| row.get(0))?;
+        urls.collect()
+    }
+
+    /// Read every visit, one row each, newest first, with how it came about and where from.
+    pub fn read_visit_log(
+        self,
+        conn: &Connection,
+        skipped: &mut Vec<rusqlite::Error>,
+    ) -> rusqlite::Result<Vec<Url>> {
+        match self {
+            Browser::Chrome => crate::chrome::read_visit_log(conn, skipped),
+            Browser::Firefox => crate::firefox::read_visit_log(conn, skipped),
+            Browser::Safari => crate::safari::read_visit_log(conn, skipped),
+        }
+    }
+
+    /// Read the searches typed into the browser's search engines, one row per results page,
+    /// newest first. Only Chrome and Chromium-based browsers record them.
+    pub fn read_searches(
+        self,
+        conn: &Connection,
+        skipped: &mut Vec<rusqlite::Error>,
+    ) -> rusqlite::Result<Vec<Url>> {
+        match self {
+            Browser::Chrome if has_table(conn, "keyword_search_terms") => {
+                crate::chrome::read_searches(conn, skipped)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Read the descriptions pages give of themselves, one row per page with [`Url::text`] set.
+    /// Only Firefox keeps them, and only since version 63.
+    pub fn read_descriptions(
+        self,
+        conn: &Connection,
+        skipped: &mut Vec<rusqlite::Error>,
+    ) -> rusqlite::Result<Vec<Url>> {
+        match self {
+            Browser::Firefox if has_column(conn, "moz_places", "description") => {
+                crate::firefox::read_descriptions(conn, skipped)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// Read bookmarks for the profile whose history database is at `history_path`.
+    ///
+    /// Bookmark rows carry no visits of their own; [`merge`] folds them into the history rows
+    /// for the same URL.
+    pub fn read_bookmarks(
+        self,
+        conn: &Connection,
+        history_path: &Path,
+        skipped: &mut Vec<rusqlite::Error>,
+    ) -> Result<Vec<Url>> {
+        match self {
+            Browser::Chrome => {
+                crate::chrome::read_bookmarks(&history_path.with_file_name("Bookmarks"))
+            }
+            Browser::Firefox => {
+                crate::firefox::read_bookmarks(conn, skipped).map_err(|source| Error::Sqlite {
+                    path: history_path.to_path_buf(),
+                    source,
+                })
+            }
+            Browser::Safari => Ok(Vec::new()),
+        }
+    }
+}
+
+pub(crate) fn has_table(conn: &Connection, name: &str) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [name],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> bool {
+    conn.query_row(
+        "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+        [table, column],
+        |_| Ok(()),
+    )
+    .is_ok()
+}
+
+/// Attach sampled visits, keyed by the browser's URL id, to the rows they belong to.
+pub fn attach_visits(urls: &mut [Url], mut visits: HashMap<i64, Vec<Visit>>) {
+    for u in urls {
+        if let Some(v) = visits.remove(&u.id) {
+            u.visits = v;
+        }
+    }
+}
+
+/// Attach visit timelines, keyed by the browser's URL id, to the rows they belong to.
+pub fn attach_timelines(urls: &mut [Url], mut timelines: HashMap<i64, Timeline>) {
+    for u in urls {
+        if let Some(t) = timelines.remove(&u.id) {
+            u.timeline = t;
+        }
+    }
+}
+
+/// Build timelines from per-URL first visits and per-URL, per-day visit counts.
+pub fn timelines(
+    first_visits: impl Iterator<Item = rusqlite::Result<(i64, Timestamp)>>,
+    daily_visits: impl Iterator<Item = rusqlite::Result<(i64, i64, u32)>>,
+) -> rusqlite::Result<HashMap<i64, Timeline>> {
+    let mut timelines: HashMap<i64, Timeline> = HashMap::new();
+    for row in first_visits {
+        let (id, time) = row?;
+        timelines.entry(id).or_default().first_visit_time = Some(time);
+    }
+    for row in daily_visits {
+        let (id, day, count) = row?;
+        timelines
+            .entry(id)
+            .or_default()
+            .daily_visits
+            .insert(day, count);
+    }
+    Ok(timelines)
+}
+
+/// Collect rows into a vector, skipping rows that fail to decode and adding their errors to
+/// `skipped`.
+pub fn collect_rows(
+    rows: impl Iterator<Item = rusqlite::Result<Url>>,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> Vec<Url> {
+    let mut urls: Vec<Url> = Vec::new();
+    for url in rows {
+        match url {
+            Ok(u) => urls.push(u),
+            Err(e) => skipped.push(e),
+        }
+    }
+    urls
+}
+
+/// Combine searches for the same normalised term, from the same or different sources.
+///
+/// The newest search keeps its results page and landing page, falling back to the landing page
+/// of an older one, and the visit counts of the results pages are summed.
+pub fn merge_searches(rows: Vec<Url>) -> Vec<Url> {
+    let mut merged: Vec<Url> = Vec::new();
+    let mut by_term: HashMap<String, usize> = HashMap::new();
+
+    for mut row in rows {
+        let Some(term) = row.search.as_ref().map(|s| s.term.clone()) else {
+            continue;
+        };
+        let Some(&i) = by_term.get(&term) else {
+            by_term.insert(term, merged.len());
+            merged.push(row);
+            continue;
+        };
+
+        let existing = &mut merged[i];
+        if row.last_visit_time > existing.last_visit_time {
+            std::mem::swap(existing, &mut row);
+        }
+        existing.visit_count += row.visit_count;
+        for source in row.sources {
+            if !existing.sources.contains(&source) {
+                existing.sources.push(source);
+            }
+        }
+        if let (Some(search), Some(older)) = (&mut existing.search, row.search)
+            && search.landing.is_none()
+        {
+            search.landing = older.landing;
+        }
+    }
+
+    merged
+}
+
+/// Combine rows for the same URL from different sources.
+///
+/// Visit and typed counts are summed, the latest visit time is kept and the visit samples are
+/// pooled. Rows keep the order they were first seen in; see [`crate::rank::sort`].
+pub fn merge(rows: Vec<Url>) -> Vec<Url> {
+    let mut merged: Vec<Url> = Vec::new();
+    let mut by_url: HashMap<String, usize> = HashMap::new();
+
+    for row in rows {
+        let Some(&i) = by_url.get(&row.url) else {
+            by_url.insert(row.url.clone(), merged.len());
+            merged.push(row);
+            continue;
+        };
+
+        let existing = &mut merged[i];
+        existing.visit_count += row.visit_count;
+        existing.typed_count += row.typed_count;
+        existing.hidden = existing.hidden.min(row.hidden);
+        if existing.bookmark.is_none() {
+            existing.bookmark = row.bookmark.clone();
+        }
+        existing.tab = existing.tab.max(row.tab);
+        if existing.title.is_empty() {
+            existing.title = row.title.clone();
+        }
+        existing.visits.extend(row.visits);
+        existing.visits.sort_by_key(|v| std::cmp::Reverse(v.time));
+        existing.visits.truncate(MAX_VISIT_SAMPLES);
+        existing.timeline.first_visit_time = match (
+            existing.timeline.first_visit_time,
+            row.timeline.first_visit_time,
+        ) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        for (day, count) in row.timeline.daily_visits {
+            *existing.timeline.daily_visits.entry(day).or_default() += count;
+        }
+        let newer = row.last_visit_time > existing.last_visit_time;
+        for source in row.sources {
+            if existing.sources.contains(&source) {
+                continue;
+            }
+            if newer {
+                existing.sources.insert(0, source);
+            } else {
+                existing.sources.push(source);
+            }
+        }
+        if newer {
+            existing.id = row.id;
+            existing.last_visit_time = row.last_visit_time;
+            if !row.title.is_empty() {
+                existing.title = row.title;
+            }
+        }
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(url: &str, browser: &str, micros: i64) -> Url {
+        Url {
+            url: url.to_string(),
+            last_visit_time: Timestamp::from_unix_micros(micros),
+            visit_count: 1,
+            sources: vec![Source {
+                browser: browser.to_string(),
+                profile: String::new(),
+                path: PathBuf::new(),
+            }],
+            ..Url::default()
+        }
+    }
+
+    fn browsers(u: &Url) -> Vec<&str> {
+        u.sources.iter().map(|s| s.browser.as_str()).collect()
+    }
+
+    #[test]
+    fn merges_rows_for_the_same_url() {
+        let chrome = Url {
+            title: "Old title".to_string(),
+            typed_count: 1,
+            bookmark: Some("Work".to_string()),
+            ..row("https://a.example/", "Chrome", 100)
+        };
+        let firefox = Url {
+            title: "New title".to_string(),
+            tab: Some(TabState::Open),
+            hidden: 1,
+            ..row("https://a.example/", "Firefox", 200)
+        };
+        let merged = merge(vec![
+            chrome,
+            row("https://b.example/", "Chrome", 50),
+            firefox,
+        ]);
+
+        assert_eq!(merged.len(), 2);
+        let a = &merged[0];
+        assert_eq!((a.visit_count, a.typed_count, a.hidden), (2, 1, 0));
+        assert_eq!(a.last_visit_time, Timestamp::from_unix_micros(200));
+        assert_eq!(a.title, "New title");
+        assert_eq!(a.bookmark.as_deref(), Some("Work"));
+        assert_eq!(a.tab, Some(TabState::Open));
+        // The source visited last comes first
+        assert_eq!(browsers(a), ["Firefox", "Chrome"]);
+        assert_eq!(merged[1].url, "https://b.example/");
+    }
+
+    #[test]
+    fn merges_searches_for_the_same_term() {
+        let search = |browser: &str, micros: i64, landing: Option<&str>| Url {
+            search: Some(SearchDetail {
+                term: "rust fts5".to_string(),
+                landing: landing.map(str::to_string),
+            }),
+            ..row("https://www.google.com/search?q=rust+fts5", browser, micros)
+        };
+        let merged = merge_searches(vec![
+            search("Chrome", 100, Some("https://sqlite.org/fts5.html")),
+            search("Edge", 300, None),
+            // Rows that are not searches are dropped
+            row("https://a.example/", "Chrome", 200),
+        ]);
+
+        assert_eq!(merged.len(), 1);
+        let s = &merged[0];
+        assert_eq!(s.visit_count, 2);
+        assert_eq!(s.last_visit_time, Timestamp::from_unix_micros(300));
+        assert_eq!(
+            s.search.as_ref().unwrap().landing.as_deref(),
+            Some("https://sqlite.org/fts5.html")
+        );
+        assert_eq!(browsers(s), ["Edge", "Chrome"]);
+    }
+}
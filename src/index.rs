@@ -0,0 +1,725 @@
+use crate::discover::Profile;
+use crate::error::{Error, Result};
+use crate::history::{self, MAX_VISIT_SAMPLES, Source, Timeline, Url};
+use crate::pages;
+use crate::search::Query;
+use crate::source::HistorySource;
+use crate::time::Timestamp;
+use rusqlite::{Connection, OptionalExtension, Row, params};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS sources (
+    id INTEGER PRIMARY KEY,
+    path TEXT NOT NULL UNIQUE,
+    browser TEXT NOT NULL,
+    profile TEXT NOT NULL,
+    -- Latest last_visit_time read from the browser, in WebKit microseconds
+    synced_until INTEGER NOT NULL DEFAULT 0,
+    -- Modification time of the browser database at the last sync, in Unix nanoseconds
+    modified INTEGER NOT NULL DEFAULT 0
+);
+CREATE TABLE IF NOT EXISTS urls (
+    source_id INTEGER NOT NULL REFERENCES sources(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    browser_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    visit_count INTEGER NOT NULL,
+    typed_count INTEGER NOT NULL,
+    last_visit_time INTEGER NOT NULL,
+    hidden INTEGER NOT NULL,
+    bookmark TEXT,
+    first_visit_time INTEGER,
+    -- JSON: the sampled visits, newest first, and visits per day
+    visits TEXT NOT NULL,
+    daily_visits TEXT NOT NULL,
+    PRIMARY KEY (source_id, url)
+);
+
+-- What --match searches: each indexed page with its stored text, kept up to date by the
+-- triggers below as pages are synced and text is stored
+CREATE VIEW IF NOT EXISTS search_content AS
+    SELECT u.rowid AS rowid, u.title, u.url, COALESCE(t.text, '') AS text
+    FROM urls u LEFT JOIN page_text t ON t.url = u.url;
+CREATE VIRTUAL TABLE IF NOT EXISTS search USING fts5(
+    title, url, text, content = 'search_content', content_rowid = 'rowid'
+);
+CREATE TRIGGER IF NOT EXISTS urls_search_insert AFTER INSERT ON urls BEGIN
+    INSERT INTO search (rowid, title, url, text)
+    VALUES (new.rowid, new.title, new.url,
+            COALESCE((SELECT text FROM page_text WHERE url = new.url), ''));
+END;
+CREATE TRIGGER IF NOT EXISTS urls_search_delete AFTER DELETE ON urls BEGIN
+    INSERT INTO search (search, rowid, title, url, text)
+    VALUES ('delete', old.rowid, old.title, old.url,
+            COALESCE((SELECT text FROM page_text WHERE url = old.url), ''));
+END;
+CREATE TRIGGER IF NOT EXISTS urls_search_update AFTER UPDATE OF title, url ON urls BEGIN
+    INSERT INTO search (search, rowid, title, url, text)
+    VALUES ('delete', old.rowid, old.title, old.url,
+            COALESCE((SELECT text FROM page_text WHERE url = old.url), ''));
+    INSERT INTO search (rowid, title, url, text)
+    VALUES (new.rowid, new.title, new.url,
+            COALESCE((SELECT text FROM page_text WHERE url = new.url), ''));
+END;
+CREATE TRIGGER IF NOT EXISTS page_text_search_insert AFTER INSERT ON page_text BEGIN
+    INSERT INTO search (search, rowid, title, url, text)
+    SELECT 'delete', rowid, title, url, '' FROM urls WHERE url = new.url;
+    INSERT INTO search (rowid, title, url, text)
+    SELECT rowid, title, url, new.text FROM urls WHERE url = new.url;
+END;
+CREATE TRIGGER IF NOT EXISTS page_text_search_delete AFTER DELETE ON page_text BEGIN
+    INSERT INTO search (search, rowid, title, url, text)
+    SELECT 'delete', rowid, title, url, old.text FROM urls WHERE url = old.url;
+    INSERT INTO search (rowid, title, url, text)
+    SELECT rowid, title, url, '' FROM urls WHERE url = old.url;
+END;
+CREATE TRIGGER IF NOT EXISTS page_text_search_update AFTER UPDATE OF text ON page_text BEGIN
+    INSERT INTO search (search, rowid, title, url, text)
+    SELECT 'delete', rowid, title, url, old.text FROM urls WHERE url = old.url;
+    INSERT INTO search (rowid, title, url, text)
+    SELECT rowid, title, url, new.text FROM urls WHERE url = new.url;
+END;
+";
+
+/// fuhl's own copy of the history of a set of profiles.
+///
+/// Each sync reads only the pages visited since the previous one, and only when the browser
+/// database or its bookmarks have changed, so startup does not have to read whole databases.
+/// Pages the browser no longer has, because it expired them or they were deleted, are dropped
+/// from the index by the next sync, unless it was told to [keep](Index::keep_expired) them.
+pub struct Index {
+    conn: Connection,
+    profiles: Vec<Profile>,
+    keep_expired: bool,
+}
+
+impl Index {
+    /// `$XDG_DATA_HOME/fuhl/index.sqlite`, else `~/.local/share/fuhl/index.sqlite`.
+    pub fn default_path() -> PathBuf {
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|p| p.is_absolute())
+            .unwrap_or_else(|| PathBuf::from(shellexpand::tilde("~/.local/share").to_string()));
+        data_home.join("fuhl").join("index.sqlite")
+    }
+
+    /// Open or create the index at `path`, for reading `profiles`.
+    pub fn open(path: &Path, profiles: Vec<Profile>) -> Result<Index> {
+        let conn = open_store(path)?;
+        conn.execute_batch("PRAGMA foreign_keys = ON")
+            .map_err(|source| Error::Sqlite {
+                path: path.to_path_buf(),
+                source,
+            })?;
+        Ok(Index {
+            conn,
+            profiles,
+            keep_expired: false,
+        })
+    }
+
+    /// Keep pages in the index after the browser forgets them, so that old history remains
+    /// searchable. History deleted in the browser then stays searchable too.
+    pub fn keep_expired(mut self, keep: bool) -> Index {
+        self.keep_expired = keep;
+        self
+    }
+
+    pub fn profiles(&self) -> &[Profile] {
+        &self.profiles
+    }
+
+    /// The URL of every page in the index.
+    pub fn indexed_urls(&self) -> Result<HashSet<String>> {
+        self.conn
+            .prepare("SELECT DISTINCT url FROM urls")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+            .map_err(|source| Error::Sqlite {
+                path: self.conn.path().map(PathBuf::from).unwrap_or_default(),
+                source,
+            })
+    }
+
+    /// The URLs of the indexed pages that match `query` by their title, URL or stored text.
+    pub fn search(&self, query: &Query) -> Result<HashSet<String>> {
+        query
+            .matching_in(&self.conn, "url")
+            .map(|urls| urls.into_iter().collect())
+            .map_err(Error::Search)
+    }
+
+    /// Bring one profile up to date, unless its database is unchanged since the last sync and
+    /// `force` is not set.
+    ///
+    /// Returns how many rows were read, or `None` when the sync was skipped.
+    pub fn sync(
+        &mut self,
+        profile: &Profile,
+        force: bool,
+        warnings: &mut Vec<Error>,
+    ) -> Result<Option<usize>> {
+        let index_path = self.conn.path().map(PathBuf::from).unwrap_or_default();
+        let sqlite = |source| Error::Sqlite {
+            path: index_path.clone(),
+            source,
+        };
+        if !profile.path.exists() {
+            return Err(Error::SourceNotFound(profile.path.clone()));
+        }
+
+        let modified = modified_nanos(&profile.path);
+        let known: Option<(i64, i64, i64)> = self
+            .conn
+            .query_row(
+                "SELECT id, synced_until, modified FROM sources WHERE path = ?1",
+                [path_key(&profile.path)],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .map_err(sqlite)?;
+        if !force && known.is_some_and(|(_, _, m)| m == modified) {
+            return Ok(None);
+        }
+        let synced_until = known.map_or(0, |(_, until, _)| until);
+
+        let since = Timestamp::from_webkit_micros(synced_until);
+        let (rows, pages) = if self.keep_expired {
+            (profile.read_urls_since(since, warnings)?, None)
+        } else {
+            let (rows, pages) = profile.read_urls_and_pages_since(since, warnings)?;
+            (rows, Some(pages))
+        };
+        let rows = history::merge(rows);
+        let count = rows.len();
+        let source = rows
+            .iter()
+            .find_map(|u| u.sources.first().cloned())
+            .unwrap_or_else(|| Source {
+                browser: profile.browser.unwrap_or_default().to_string(),
+                profile: profile.profile.clone(),
+                path: profile.path.clone(),
+            });
+        let latest = rows
+            .iter()
+            .map(|u| u.last_visit_time.webkit_micros())
+            .max()
+            .unwrap_or(0)
+            .max(synced_until);
+
+        let tx = self.conn.transaction().map_err(sqlite)?;
+        tx.execute(
+            "INSERT INTO sources (path, browser, profile) VALUES (?1, ?2, ?3)
+             ON CONFLICT (path) DO UPDATE SET browser = ?2, profile = ?3",
+            params![path_key(&profile.path), source.browser, source.profile],
+        )
+        .map_err(sqlite)?;
+        let source_id: i64 = tx
+            .query_row(
+                "SELECT id FROM sources WHERE path = ?1",
+                [path_key(&profile.path)],
+                |row| row.get(0),
+            )
+            .map_err(sqlite)?;
+        // Every bookmark is read on each sync, so ones that were removed go away
+        tx.execute(
+            "UPDATE urls SET bookmark = NULL WHERE source_id = ?1",
+            [source_id],
+        )
+        .map_err(sqlite)?;
+        for row in rows {
+            let existing = tx
+                .query_row(
+                    "SELECT * FROM urls WHERE source_id = ?1 AND url = ?2",
+                    params![source_id, row.url],
+                    read_row,
+                )
+                .optional()
+                .map_err(sqlite)?;
+            let u = match existing {
+                Some(old) => combine(old, row),
+                None => row,
+            };
+            write_row(&tx, source_id, &u).map_err(sqlite)?;
+        }
+        if let Some(pages) = pages {
+            // Bookmarks were all just read, so only pages the browser forgot are left to drop
+            let indexed: Vec<String> = tx
+                .prepare("SELECT url FROM urls WHERE source_id = ?1 AND bookmark IS NULL")
+                .and_then(|mut stmt| stmt.query_map([source_id], |row| row.get(0))?.collect())
+                .map_err(sqlite)?;
+            for url in indexed.iter().filter(|url| !pages.contains(*url)) {
+                tx.execute(
+                    "DELETE FROM urls WHERE source_id = ?1 AND url = ?2",
+                    params![source_id, url],
+                )
+                .map_err(sqlite)?;
+            }
+        }
+        tx.execute(
+            "UPDATE sources SET synced_until = ?2, modified = ?3 WHERE id = ?1",
+            params![source_id, latest, modified],
+        )
+        .map_err(sqlite)?;
+        tx.commit().map_err(sqlite)?;
+        Ok(Some(count))
+    }
+
+    /// Sync every profile, adding the ones that fail to `warnings`; the index still has what
+    /// they had.
+    pub fn sync_all(&mut self, force: bool, warnings: &mut Vec<Error>) {
+        for profile in self.profiles.clone() {
+            if let Err(e) = self.sync(&profile, force, warnings) {
+                warnings.push(e);
+            }
+        }
+    }
+}
+
+impl HistorySource for Index {
+    /// Every indexed page of the index's profiles, one row per profile.
+    fn read_urls(&self, warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        let index_path = self.conn.path().map(PathBuf::from).unwrap_or_default();
+        let sqlite = |source| Error::Sqlite {
+            path: index_path.clone(),
+            source,
+        };
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT u.*, s.browser, s.profile FROM urls u
+                 JOIN sources s ON s.id = u.source_id
+                 WHERE s.path = ?1",
+            )
+            .map_err(sqlite)?;
+
+        let mut urls = Vec::new();
+        for profile in &self.profiles {
+            let rows = stmt
+                .query_map([path_key(&profile.path)], |row| {
+                    let mut u = read_row(row)?;
+                    u.sources.push(Source {
+                        browser: row.get("browser")?,
+                        profile: row.get("profile")?,
+                        path: profile.path.clone(),
+                    });
+                    Ok(u)
+                })
+                .map_err(sqlite)?;
+            let mut skipped = Vec::new();
+            urls.extend(history::collect_rows(rows, &mut skipped));
+            warnings.extend(skipped.into_iter().map(sqlite));
+        }
+        Ok(urls)
+    }
+}
+
+/// Fold a fresh read of a page into what the index had for it.
+///
+/// Browsers forget old visits, so counts never go down and visits are pooled. A page that was
+/// only read as a bookmark, with no visits since the last sync, keeps the id and title from its
+/// history; the bookmark's name is only used for pages without a title.
+fn combine(old: Url, new: Url) -> Url {
+    let mut visits = old.visits;
+    for v in new.visits {
+        if !visits.contains(&v) {
+            visits.push(v);
+        }
+    }
+    visits.sort_by_key(|v| std::cmp::Reverse(v.time));
+    visits.truncate(MAX_VISIT_SAMPLES);
+
+    let mut daily_visits = old.timeline.daily_visits;
+    for (day, count) in new.timeline.daily_visits {
+        let entry = daily_visits.entry(day).or_default();
+        *entry = (*entry).max(count);
+    }
+    let first_visit_time = match (old.timeline.first_visit_time, new.timeline.first_visit_time) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    };
+
+    let visited = !new.last_visit_time.is_never();
+    Url {
+        id: if visited { new.id } else { old.id },
+        title: if old.title.is_empty() || visited && !new.title.is_empty() {
+            new.title
+        } else {
+            old.title
+        },
+        visit_count: old.visit_count.max(new.visit_count),
+        typed_count: old.typed_count.max(new.typed_count),
+        last_visit_time: old.last_visit_time.max(new.last_visit_time),
+        hidden: new.hidden,
+        bookmark: new.bookmark,
+        visits,
+        timeline: Timeline {
+            first_visit_time,
+            daily_visits,
+        },
+        ..new
+    }
+}
+
+fn read_row(row: &Row) -> rusqlite::Result<Url> {
+    let json = |column: &str| -> rusqlite::Result<String> { row.get(column) };
+    let decode = |e: serde_json::Error| {
+        rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::new(e))
+    };
+    Ok(Url {
+        id: row.get("browser_id")?,
+        url: row.get("url")?,
+        title: row.get("title")?,
+        visit_count: row.get("visit_count")?,
+        typed_count: row.get("typed_count")?,
+        last_visit_time: Timestamp::from_webkit_micros(row.get("last_visit_time")?),
+        hidden: row.get("hidden")?,
+        sources: Vec::new(),
+        bookmark: row.get("bookmark")?,
+        visits: serde_json::from_str(&json("visits")?).map_err(decode)?,
+        timeline: Timeline {
+            first_visit_time: row
+                .get::<_, Option<i64>>("first_visit_time")?
+                .map(Timestamp::from_webkit_micros),
+            daily_visits: serde_json::from_str(&json("daily_visits")?).map_err(decode)?,
+        },
+        visit: None,
+        text: None,
+        search: None,
+        tab: None,
+    })
+}
+
+fn write_row(conn: &Connection, source_id: i64, u: &Url) -> rusqlite::Result<()> {
+    let visits = serde_json::to_string(&u.visits).expect("visits serialize to JSON");
+    let daily_visits =
+        serde_json::to_string(&u.timeline.daily_visits).expect("day counts serialize to JSON");
+    conn.execute(
+        "INSERT INTO urls (source_id, url, browser_id, title, visit_count, typed_count,
+             last_visit_time, hidden, bookmark, first_visit_time, visits, daily_visits)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+         ON CONFLICT (source_id, url) DO UPDATE SET browser_id = ?3, title = ?4,
+             visit_count = ?5, typed_count = ?6, last_visit_time = ?7, hidden = ?8,
+             bookmark = ?9, first_visit_time = ?10, visits = ?11, daily_visits = ?12",
+        params![
+            source_id,
+            u.url,
+            u.id,
+            u.title,
+            u.visit_count,
+            u.typed_count,
+            u.last_visit_time.webkit_micros(),
+            u.hidden,
+            u.bookmark,
+            u.timeline.first_visit_time.map(Timestamp::webkit_micros),
+            visits,
+            daily_visits,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Open or create fuhl's store, readable only by the current user, with the tables of both the
+/// index and the page text, whose search table spans the two.
+pub(crate) fn open_store(path: &Path) -> Result<Connection> {
+    if let Some(dir) = path.parent() {
+        create_private_dir(dir).map_err(|source| Error::Io {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+    }
+    let conn = Connection::open(path).map_err(|source| Error::Sqlite {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let private = std::fs::Permissions::from_mode(0o600);
+        std::fs::set_permissions(path, private).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    }
+    create_tables(&conn).map_err(|source| Error::Sqlite {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    Ok(conn)
+}
+
+fn create_tables(conn: &Connection) -> rusqlite::Result<()> {
+    let searchable = history::has_table(conn, "search");
+    conn.execute_batch(pages::SCHEMA)?;
+    conn.execute_batch(SCHEMA)?;
+    // Stores from before the search table have pages and text for it to catch up on
+    if !searchable {
+        conn.execute("INSERT INTO search (search) VALUES ('rebuild')", [])?;
+    }
+    Ok(())
+}
+
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+/// The latest modification time of a database, its `-wal` file and a Chrome `Bookmarks` file
+/// next to it, in Unix nanoseconds.
+fn modified_nanos(path: &Path) -> i64 {
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    [
+        path.to_path_buf(),
+        PathBuf::from(wal),
+        path.with_file_name("Bookmarks"),
+    ]
+    .iter()
+    .filter_map(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok())
+    .filter_map(|t| t.duration_since(UNIX_EPOCH).ok())
+    .map(|d| d.as_nanos() as i64)
+    .max()
+    .unwrap_or(0)
+}
+
+fn create_private_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{Transition, Visit};
+    use crate::pages::PageText;
+
+    fn row(visit_count: i64, days: &[i64]) -> Url {
+        Url {
+            url: "https://example.com/".to_string(),
+            title: "Example".to_string(),
+            visit_count,
+            last_visit_time: Timestamp::from_webkit_micros(days[0] * 86_400_000_000),
+            visits: days
+                .iter()
+                .map(|&d| Visit {
+                    time: Timestamp::from_webkit_micros(d * 86_400_000_000),
+                    transition: Transition::Link,
+                })
+                .collect(),
+            ..Url::default()
+        }
+    }
+
+    #[test]
+    fn keeps_visits_the_browser_expired() {
+        // The browser has forgotten the visits from days 1 and 2
+        let u = combine(row(3, &[3, 2, 1]), row(2, &[5, 3]));
+        assert_eq!(u.visit_count, 3);
+        assert_eq!(
+            u.last_visit_time,
+            Timestamp::from_webkit_micros(5 * 86_400_000_000)
+        );
+        assert_eq!(u.visits.len(), 4);
+        assert_eq!(
+            u.visits[0].time,
+            Timestamp::from_webkit_micros(5 * 86_400_000_000)
+        );
+    }
+
+    #[test]
+    fn bookmarks_keep_the_history_title_and_id() {
+        let history = Url {
+            id: 7,
+            ..row(3, &[3])
+        };
+        let bookmark = Url {
+            title: "My bookmark".to_string(),
+            bookmark: Some("Bookmarks bar".to_string()),
+            ..Url::default()
+        };
+        let u = combine(history, bookmark);
+        assert_eq!((u.id, u.title.as_str()), (7, "Example"));
+        assert_eq!(u.bookmark.as_deref(), Some("Bookmarks bar"));
+        assert_eq!(u.visit_count, 3);
+
+        let untitled = Url {
+            title: String::new(),
+            ..row(3, &[3])
+        };
+        let bookmark = Url {
+            title: "My bookmark".to_string(),
+            ..Url::default()
+        };
+        assert_eq!(combine(untitled, bookmark).title, "My bookmark");
+    }
+
+    /// A Chrome history database in `dir` with two visited pages.
+    fn chrome_history(dir: &Path) -> (Profile, Connection) {
+        let history = dir.join("History");
+        let browser = Connection::open(&history).unwrap();
+        browser
+            .execute_batch(
+                "CREATE TABLE urls (id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR,
+                     visit_count INTEGER, typed_count INTEGER, last_visit_time INTEGER,
+                     hidden INTEGER);
+                 CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER, visit_time INTEGER,
+                     from_visit INTEGER, transition INTEGER);
+                 INSERT INTO urls VALUES (1, 'https://kept.example/', 'Kept', 1, 0, 100, 0);
+                 INSERT INTO urls VALUES (2, 'https://deleted.example/', 'Deleted', 1, 0, 200, 0);
+                 INSERT INTO visits VALUES (1, 1, 100, 0, 1);
+                 INSERT INTO visits VALUES (2, 2, 200, 0, 1);",
+            )
+            .unwrap();
+        (Profile::from_path(history), browser)
+    }
+
+    #[test]
+    fn drops_pages_the_browser_no_longer_has() {
+        let dir = tempfile::tempdir().unwrap();
+        let (profile, browser) = chrome_history(dir.path());
+        let synced = |keep_expired: bool| -> Vec<String> {
+            let path = dir.path().join(format!("index-{}.sqlite", keep_expired));
+            let mut index = Index::open(&path, vec![profile.clone()])
+                .unwrap()
+                .keep_expired(keep_expired);
+            index.sync(&profile, true, &mut Vec::new()).unwrap();
+            let mut urls: Vec<String> = index
+                .read_urls(&mut Vec::new())
+                .unwrap()
+                .into_iter()
+                .map(|u| u.url)
+                .collect();
+            urls.sort();
+            urls
+        };
+        assert_eq!(synced(false).len(), 2);
+        assert_eq!(synced(true).len(), 2);
+
+        browser
+            .execute_batch("DELETE FROM urls WHERE id = 2; DELETE FROM visits WHERE url = 2;")
+            .unwrap();
+        assert_eq!(synced(false), ["https://kept.example/"]);
+        assert_eq!(
+            synced(true),
+            ["https://deleted.example/", "https://kept.example/"]
+        );
+    }
+
+    #[test]
+    fn search_follows_syncs_and_page_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let (profile, browser) = chrome_history(dir.path());
+        let path = dir.path().join("index.sqlite");
+        let mut index = Index::open(&path, vec![profile.clone()]).unwrap();
+        index.sync(&profile, true, &mut Vec::new()).unwrap();
+        let store = PageText::open(&path).unwrap();
+        store
+            .store("https://kept.example/", "quarterly capacity plan")
+            .unwrap();
+        let search = |index: &Index, query: &str| -> Vec<String> {
+            let mut urls: Vec<String> = index
+                .search(&query.parse().unwrap())
+                .unwrap()
+                .into_iter()
+                .collect();
+            urls.sort();
+            urls
+        };
+
+        assert_eq!(search(&index, "capacity"), ["https://kept.example/"]);
+        assert_eq!(
+            search(&index, "title:deleted"),
+            ["https://deleted.example/"]
+        );
+        assert_eq!(
+            search(&index, "-text:capacity"),
+            ["https://deleted.example/"]
+        );
+        store
+            .store("https://kept.example/", "incident review")
+            .unwrap();
+        assert!(search(&index, "capacity").is_empty());
+        assert_eq!(search(&index, "text:incident"), ["https://kept.example/"]);
+
+        browser
+            .execute_batch(
+                "DELETE FROM urls WHERE id = 2; DELETE FROM visits WHERE url = 2;
+                 UPDATE urls SET title = 'Renamed', last_visit_time = 300 WHERE id = 1;
+                 INSERT INTO visits VALUES (3, 1, 300, 0, 1);",
+            )
+            .unwrap();
+        index.sync(&profile, true, &mut Vec::new()).unwrap();
+        assert!(search(&index, "deleted").is_empty());
+        assert_eq!(
+            search(&index, "renamed incident"),
+            ["https://kept.example/"]
+        );
+        store.clear().unwrap();
+        assert!(search(&index, "incident").is_empty());
+        index
+            .conn
+            .execute("INSERT INTO search (search) VALUES ('integrity-check')", [])
+            .unwrap();
+
+        // Rows the index does not have, such as open tabs, are still searched
+        let query: Query = "renamed OR tab".parse().unwrap();
+        let mut urls = vec![
+            Url {
+                url: "https://kept.example/".to_string(),
+                ..Url::default()
+            },
+            Url {
+                url: "https://tab.example/".to_string(),
+                title: "Tab".to_string(),
+                ..Url::default()
+            },
+        ];
+        query.retain(&mut urls, Some(&index)).unwrap();
+        assert_eq!(urls.len(), 2);
+    }
+
+    #[test]
+    fn bookmark_edits_count_as_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = dir.path().join("History");
+        let bookmarks = dir.path().join("Bookmarks");
+        let set_modified = |path: &Path, secs: u64| {
+            let file = std::fs::File::create(path).unwrap();
+            let time = UNIX_EPOCH + std::time::Duration::from_secs(secs);
+            file.set_modified(time).unwrap();
+        };
+        set_modified(&history, 1_000);
+        set_modified(&bookmarks, 900);
+        let before = modified_nanos(&history);
+        set_modified(&bookmarks, 2_000);
+        assert!(modified_nanos(&history) > before);
+    }
+
+    #[test]
+    fn round_trips_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_tables(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO sources (id, path, browser, profile) VALUES (1, '/h', 'Chrome', 'Default')",
+            [],
+        )
+        .unwrap();
+        let mut u = row(3, &[3, 2, 1]);
+        u.bookmark = Some("Bookmarks bar".to_string());
+        u.timeline.daily_visits.insert(3, 2);
+        write_row(&conn, 1, &u).unwrap();
+
+        let read = conn.query_row("SELECT * FROM urls", [], read_row).unwrap();
+        assert_eq!(read.url, u.url);
+        assert_eq!(read.visits, u.visits);
+        assert_eq!(read.timeline.daily_visits, u.timeline.daily_visits);
+        assert_eq!(read.bookmark, u.bookmark);
+    }
+}
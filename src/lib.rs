@@ -0,0 +1,68 @@
+//! Search browser history and bookmarks.
+//!
+//! A [`HistorySource`] yields [`Url`] rows; browser profiles found by
+//! [`discover::history_databases`] are sources, and so is anything else that implements the
+//! trait. [`source::load_all`] reads and merges them, [`index::Index`] keeps a fast local copy
+//! of them, [`search`] runs full-text queries over them, [`rank`] orders them, and [`picker`],
+//! [`headless`] and [`display`] present them.
+//!
+//! ```no_run
+//! use fuhl::source::Mode;
+//! use fuhl::{Error, HistorySource, Result, Url, rank};
+//!
+//! struct Pinned;
+//!
+//! impl HistorySource for Pinned {
+//!     fn read_urls(&self, _warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+//!         Ok(vec![Url {
+//!             url: "https://intranet.example.com/".to_string(),
+//!             title: "Intranet".to_string(),
+//!             ..Url::default()
+//!         }])
+//!     }
+//! }
+//!
+//! let mut sources: Vec<Box<dyn HistorySource>> = vec![Box::new(Pinned)];
+//! for profile in fuhl::discover::history_databases() {
+//!     sources.push(Box::new(profile));
+//! }
+//! let mut warnings = Vec::new();
+//! let mut urls = fuhl::source::load_all(&sources, Mode::Pages, &mut warnings)?;
+//! for warning in &warnings {
+//!     eprintln!("{}", warning);
+//! }
+//! rank::sort(&mut urls, rank::SortOrder::Frecency, &rank::Weights::default());
+//! for u in fuhl::headless::matches(&urls, "intranet") {
+//!     println!("{}", u.url);
+//! }
+//! # Ok::<(), fuhl::Error>(())
+//! ```
+
+pub mod action;
+mod chrome;
+mod clipboard;
+pub mod config;
+pub mod discover;
+pub mod display;
+pub mod error;
+pub mod filter;
+mod firefox;
+pub mod headless;
+pub mod history;
+pub mod index;
+mod mozlz4;
+pub mod open;
+pub mod pages;
+pub mod picker;
+pub mod rank;
+mod safari;
+pub mod search;
+mod snapshot;
+mod snss;
+pub mod source;
+pub mod tabs;
+pub mod time;
+
+pub use error::{Error, Result};
+pub use history::Url;
+pub use source::HistorySource;
@@ -1,100 +1,340 @@
-use skim::prelude::*;
-use std::io::Cursor;
-
-#[derive(Debug)]
-struct Url {
-    id: i64,
-    url: String,
-    title: String,
-    visit_count: i64,
-    typed_count: i64,
-    last_visit_time: i64,
-    hidden: i64,
-}
+mod cli;
+mod shell;
+
+use cli::{Command, ConfigCommand, IndexCommand, TextCommand};
+use fuhl::config::Config;
+use fuhl::discover::{self, Profile};
+use fuhl::filter::Filter;
+use fuhl::index::Index;
+use fuhl::pages::{self, PageText};
+use fuhl::picker::{self, PickOptions};
+use fuhl::source::Mode;
+use fuhl::tabs::Tabs;
+use fuhl::{Error, HistorySource, Result, Url, action, display, headless, history, rank, source};
+use std::io::Write;
+use std::sync::Arc;
 
 fn main() {
-    let database_file = std::env::var("FUHL_DB").unwrap_or_else(|_| {
-        if cfg!(target_os = "macos") {
-            let path = "~/Library/Application Support/Google/Chrome/Default/History";
-            shellexpand::tilde(path).to_string()
+    if let Err(e) = run() {
+        eprintln!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run() -> Result<()> {
+    let args = cli::parse();
+    // The widget does not depend on the settings, so a broken config file cannot break the shell
+    if let Some(Command::Init { shell }) = args.command {
+        print!("{}", shell::widget(shell));
+        return Ok(());
+    }
+    let mut config = Config::load()?;
+    config.apply_env();
+    args.apply(&mut config);
+
+    let mut filter = config
+        .filter
+        .to_filter()
+        .expect("Filter patterns are checked when they are read");
+    (filter.since, filter.before) = args.filter.time_range();
+    filter.tabs = args.filter.tabs;
+
+    let profiles: Vec<Profile> = if config.sources.is_empty() {
+        discover::history_databases()
+    } else {
+        config
+            .sources
+            .iter()
+            .cloned()
+            .map(Profile::from_path)
+            .collect()
+    };
+
+    match &args.command {
+        Some(Command::Config {
+            command: ConfigCommand::Show,
+        }) => {
+            println!("# {}\n", Config::path().display());
+            print!("{}", config.to_toml());
+            return Ok(());
+        }
+        Some(Command::Index { command }) => return index_command(command, &config, profiles),
+        Some(Command::Text { command }) => {
+            return text_command(command, &config, profiles, &filter);
+        }
+        Some(Command::Init { .. }) | None => {}
+    }
+    if profiles.is_empty() {
+        return Err(Error::NoSources);
+    }
+
+    let mode = args.mode();
+    let mut warnings = Vec::new();
+    let sources = sources(&config, profiles.clone(), mode, &mut warnings);
+    let loaded = source::load_all(&sources, mode, &mut warnings);
+    report(&warnings);
+    let mut urls = loaded?;
+    if mode == Mode::Pages {
+        // Tabs are a bonus; history is still worth showing when they cannot be read
+        let tabs: Vec<Box<dyn HistorySource>> = profiles
+            .into_iter()
+            .map(|p| Box::new(Tabs::new(p)) as Box<dyn HistorySource>)
+            .collect();
+        let mut warnings = Vec::new();
+        match source::load_all(&tabs, Mode::Pages, &mut warnings) {
+            Ok(tabs) => urls = history::merge(urls.into_iter().chain(tabs).collect()),
+            Err(e) => warnings.push(e),
+        }
+        report(&warnings);
+    }
+    urls.retain(|u| filter.matches(u));
+    // Page text lives next to the index; there is none if the store was never created
+    if config.index.path.exists()
+        && let Err(e) = PageText::open(&config.index.path).and_then(|s| s.attach(&mut urls))
+    {
+        eprintln!("{}", e);
+    }
+    if let Some(query) = &args.match_query {
+        // The index searches the text of its pages without reading it all in
+        let index = (config.index.enabled && mode == Mode::Pages)
+            .then(|| Index::open(&config.index.path, Vec::new()).ok())
+            .flatten();
+        query.retain(&mut urls, index.as_ref())?;
+    }
+    // Visits and searches are always listed in time order
+    let order = match mode {
+        Mode::Pages => config.rank.sort,
+        Mode::Visits | Mode::Searches => rank::SortOrder::Recent,
+    };
+    rank::sort(&mut urls, order, &config.rank.weights);
+    if urls.is_empty() {
+        return Err(Error::NoMatches);
+    }
+
+    if args.headless() {
+        let query = args.query().unwrap_or_default();
+        let matches = if config.picker.page_text {
+            headless::matches_page_text(&urls, &query)
         } else {
-            "none".to_string()
+            headless::matches(&urls, &query)
+        };
+        if matches.is_empty() {
+            return Err(Error::NoMatches);
         }
-    });
-    if std::path::Path::new(&database_file).exists() {
-        std::fs::copy(&database_file, "/tmp/fuhl").expect("Failed to copy database file");
-    } else {
-        eprintln!("History DB not found at path {}", database_file);
-        std::process::exit(1);
+        for u in matches.into_iter().take(args.limit.unwrap_or(usize::MAX)) {
+            println!("{}", display::render(&config.display.format, u));
+        }
+        return Ok(());
     }
 
-    let conn = rusqlite::Connection::open("/tmp/fuhl").expect("Failed to open database");
-    let mut stmt = conn.prepare("SELECT id, url, title, visit_count, typed_count, last_visit_time, hidden FROM urls WHERE length(url) < 60 ORDER BY last_visit_time DESC, visit_count DESC").expect("Failed to prepare statement");
-
-    let url_iter = stmt
-        .query_map([], |row| {
-            Ok(Url {
-                id: row.get(0)?,
-                url: row.get(1)?,
-                title: row.get(2)?,
-                visit_count: row.get(3)?,
-                typed_count: row.get(4)?,
-                last_visit_time: row.get(5)?,
-                hidden: row.get(6)?,
-            })
-        })
-        .expect("Failed to query urls");
-
-    // Collect rows into a vector so we can present them to skim and map back to the Url
-    let mut urls: Vec<Url> = Vec::new();
-    for url in url_iter {
-        match url {
-            Ok(u) => urls.push(u),
-            Err(e) => eprintln!("Error reading row: {}", e),
+    let urls = Arc::new(urls);
+    let options = PickOptions {
+        query: args.query(),
+        select_1: args.select_1,
+        exit_0: args.exit_0,
+    };
+    let selection = picker::pick(&urls, &config, &options)?;
+    let mut selected: Vec<&Url> = selection.indexes.iter().map(|&i| &urls[i]).collect();
+    if selection.action.opens() {
+        if selected.len() > config.open.max_open {
+            eprintln!(
+                "Only opening the first {} of {} selected URLs",
+                config.open.max_open,
+                selected.len()
+            );
+            selected.truncate(config.open.max_open);
         }
+        if selected.len() > config.open.confirm_above && !confirm(selected.len()) {
+            return Ok(());
+        }
+    }
+    let mut warnings = Vec::new();
+    let result = action::run(
+        selection.action,
+        &selected,
+        &config.open_options(),
+        &mut warnings,
+    );
+    report(&warnings);
+    result
+}
+
+/// Ask on the terminal whether to go ahead with opening `count` URLs.
+fn confirm(count: usize) -> bool {
+    eprint!("Open {} URLs? [y/N] ", count);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
     }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
 
-    if urls.is_empty() {
-        eprintln!("No URLs found");
-        return;
+/// Print problems that did not stop fuhl, such as a profile that could not be read.
+fn report(warnings: &[Error]) {
+    for warning in warnings {
+        eprintln!("{}", warning);
     }
+}
 
-    // Build the input lines for skim. Prefix each line with the index so we can find the selected item.
-    let mut input = String::new();
-    for (i, u) in urls.iter().enumerate() {
-        let safe_url = u.url.replace('\n', " ");
-        let safe_title = u.title.replace('\n', " ");
-        input.push_str(&format!("{}\t{} ... {}\n", i, safe_title, safe_url));
+/// The sources to read: the index, kept in sync with `profiles`, or the profiles themselves.
+fn sources(
+    config: &Config,
+    profiles: Vec<Profile>,
+    mode: Mode,
+    warnings: &mut Vec<Error>,
+) -> Vec<Box<dyn HistorySource>> {
+    // Only pages are indexed, and a broken index should not stop fuhl from working
+    let index = if config.index.enabled && mode == Mode::Pages {
+        Index::open(&config.index.path, profiles.clone())
+            .map(|index| index.keep_expired(config.index.keep_expired))
+            .inspect_err(|e| eprintln!("{}; reading the browsers directly", e))
+            .ok()
+    } else {
+        None
+    };
+    match index {
+        Some(mut index) => {
+            index.sync_all(false, warnings);
+            vec![Box::new(index)]
+        }
+        None => profiles
+            .into_iter()
+            .map(|p| Box::new(p) as Box<dyn HistorySource>)
+            .collect(),
     }
+}
 
-    // Configure skim options: single-select, reasonable height
-    let options = SkimOptionsBuilder::default()
-        .height("50%".to_string())
-        .multi(false)
-        .build()
-        .unwrap();
-
-    // Run skim with our input
-    let item_reader = SkimItemReader::default();
-    let items = item_reader.of_bufread(Cursor::new(input));
-    let selected_items = Skim::run_with(&options, Some(items))
-        .map(|out| out.selected_items)
-        .unwrap_or_default();
-
-    if selected_items.is_empty() {
-        eprintln!("No selection made");
-        return;
+fn index_command(command: &IndexCommand, config: &Config, profiles: Vec<Profile>) -> Result<()> {
+    let path = &config.index.path;
+    match command {
+        IndexCommand::Sync => {
+            let mut index =
+                Index::open(path, profiles.clone())?.keep_expired(config.index.keep_expired);
+            for profile in &profiles {
+                let mut warnings = Vec::new();
+                let synced = index.sync(profile, true, &mut warnings);
+                report(&warnings);
+                match synced {
+                    Ok(count) => println!(
+                        "{}: {} rows",
+                        profile.path.display(),
+                        count.unwrap_or_default()
+                    ),
+                    Err(e) => eprintln!("{}", e),
+                }
+            }
+        }
+        IndexCommand::Clear => match std::fs::remove_file(path) {
+            Ok(()) => println!("Removed {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(source) => {
+                return Err(Error::Io {
+                    path: path.clone(),
+                    source,
+                });
+            }
+        },
     }
+    Ok(())
+}
 
-    // Parse the selected line to get the index and open the corresponding URL in the default browser
-    let selected_output = selected_items[0].output();
-    let parts: Vec<&str> = selected_output.split("\t").collect();
-    let idx: usize = parts.get(0).and_then(|s| s.parse().ok()).unwrap_or(0);
-    if let Some(u) = urls.get(idx) {
-        // Open the URL in the default browser
-        match webbrowser::open(&u.url) {
-            Ok(_) => {}
-            Err(e) => eprintln!("Failed to open URL {}: {}", u.url, e),
+fn text_command(
+    command: &TextCommand,
+    config: &Config,
+    profiles: Vec<Profile>,
+    filter: &Filter,
+) -> Result<()> {
+    let store = PageText::open(&config.index.path)?;
+    match command {
+        TextCommand::Fetch {
+            urls,
+            bookmarks,
+            min_visits,
+            refetch,
+        } => {
+            let mut wanted = urls.clone();
+            if *bookmarks || min_visits.is_some() {
+                let mut warnings = Vec::new();
+                let sources = sources(config, profiles, Mode::Pages, &mut warnings);
+                let history = source::load_all(&sources, Mode::Pages, &mut warnings);
+                report(&warnings);
+                let history = history?;
+                wanted.extend(
+                    history
+                        .into_iter()
+                        .filter(|u| filter.matches(u))
+                        .filter(|u| {
+                            (*bookmarks && u.bookmark.is_some())
+                                || min_visits.is_some_and(|n| u.visit_count >= n)
+                        })
+                        .map(|u| u.url),
+                );
+            }
+
+            let mut seen = std::collections::HashSet::new();
+            wanted.retain(|url| seen.insert(url.clone()));
+
+            if !refetch {
+                let mut missing = Vec::new();
+                for url in wanted {
+                    if !store.contains(&url)? {
+                        missing.push(url);
+                    }
+                }
+                wanted = missing;
+            }
+            let already = seen.len() - wanted.len();
+
+            let mut fetched = 0;
+            let mut failed = 0;
+            pages::fetch_all(&wanted, |url, text| {
+                match text {
+                    Ok(text) => {
+                        store.store(url, &text)?;
+                        println!("{}: {} characters", url, text.chars().count());
+                        fetched += 1;
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        failed += 1;
+                    }
+                }
+                Ok(())
+            })?;
+            println!(
+                "Stored text for {} pages, {} failed, {} already stored",
+                fetched, failed, already
+            );
+        }
+        TextCommand::Import => {
+            // Descriptions are not indexed, so they are read from the browsers
+            let mut warnings = Vec::new();
+            let mut imported = 0;
+            for profile in &profiles {
+                let pages = match profile.read_descriptions(&mut warnings) {
+                    Ok(pages) => pages,
+                    Err(e) => {
+                        warnings.push(e);
+                        continue;
+                    }
+                };
+                for u in pages.iter().filter(|u| filter.matches(u)) {
+                    if let Some(text) = &u.text
+                        && !store.contains(&u.url)?
+                    {
+                        store.store(&u.url, text)?;
+                        imported += 1;
+                    }
+                }
+            }
+            report(&warnings);
+            println!("Stored the descriptions of {} pages", imported);
+        }
+        TextCommand::Clear => {
+            let count = store.clear()?;
+            println!("Removed the text of {} pages", count);
         }
     }
+    Ok(())
 }
@@ -0,0 +1,99 @@
+const MAGIC: &[u8] = b"mozLz40\0";
+
+/// Decompress the contents of a Firefox `.jsonlz4` file: a magic number, the decompressed size
+/// and one LZ4 block.
+pub fn decompress(file: &[u8]) -> Result<Vec<u8>, String> {
+    let rest = file
+        .strip_prefix(MAGIC)
+        .ok_or_else(|| "not a mozLz4 file".to_string())?;
+    if rest.len() < 4 {
+        return Err("truncated header".to_string());
+    }
+    let size = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+    let out = decompress_block(&rest[4..], size)?;
+    if out.len() != size {
+        return Err(format!("expected {} bytes, got {}", size, out.len()));
+    }
+    Ok(out)
+}
+
+/// Decode one LZ4 block: runs of literals, each followed by a copy of earlier output.
+fn decompress_block(block: &[u8], size: usize) -> Result<Vec<u8>, String> {
+    let truncated = || "truncated LZ4 block".to_string();
+    // The size comes from the file; each input byte can expand to at most 255 output bytes
+    let mut out: Vec<u8> = Vec::with_capacity(size.min(block.len().saturating_mul(255)));
+    let mut i = 0;
+
+    // A length of 15 continues in the following bytes, each adding up to 255
+    let length = |start: usize, i: &mut usize| -> Result<usize, String> {
+        let mut len = start;
+        if start == 15 {
+            loop {
+                let byte = *block.get(*i).ok_or_else(truncated)?;
+                *i += 1;
+                len += byte as usize;
+                if byte != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(len)
+    };
+
+    while i < block.len() {
+        let token = block[i];
+        i += 1;
+
+        let literals = length((token >> 4) as usize, &mut i)?;
+        let end = i + literals;
+        out.extend_from_slice(block.get(i..end).ok_or_else(truncated)?);
+        i = end;
+        // The last sequence has literals only
+        if i == block.len() {
+            break;
+        }
+
+        let offset = block
+            .get(i..i + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+            .ok_or_else(truncated)?;
+        i += 2;
+        if offset == 0 || offset > out.len() {
+            return Err(format!("invalid LZ4 match offset {}", offset));
+        }
+        let matched = length((token & 0x0f) as usize, &mut i)? + 4;
+        if out.len() + matched > size {
+            return Err("LZ4 block is longer than its header says".to_string());
+        }
+        // Copies may overlap the bytes they produce, so go one byte at a time
+        let start = out.len() - offset;
+        for k in 0..matched {
+            out.push(out[start + k]);
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_literals_and_overlapping_matches() {
+        // "abcabcabcabc!" as 3 literals, a 9 byte match at offset 3, then "!"
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&13u32.to_le_bytes());
+        file.extend_from_slice(&[0x35, b'a', b'b', b'c', 3, 0, 0x10, b'!']);
+        assert_eq!(decompress(&file).unwrap(), b"abcabcabcabc!");
+
+        file.truncate(file.len() - 3);
+        assert!(decompress(&file).is_err());
+        assert!(decompress(b"{\"windows\": []}").is_err());
+
+        // A size far beyond what the block can hold is not allocated up front
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&u32::MAX.to_le_bytes());
+        file.extend_from_slice(&[0x10, b'!']);
+        assert!(decompress(&file).is_err());
+    }
+}
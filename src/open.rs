@@ -0,0 +1,476 @@
+use crate::error::{Error, Result};
+use crate::history::{Source, Url};
+use serde::{Deserialize, Serialize};
+use std::process::{Command, Stdio};
+
+/// How a browser expects to be told about profiles, windows and private mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    /// `--profile-directory=DIR`, and `--new-window url...` opens every URL in one new window.
+    Chromium,
+    /// `--profile DIR`, and `--new-window url` opens one URL; `--new-tab url` adds the rest to it.
+    Firefox,
+}
+
+/// A browser fuhl can start directly.
+struct Launcher {
+    /// Matches [`Source::browser`].
+    name: &'static str,
+    family: Family,
+    linux: &'static str,
+    macos: &'static str,
+    private: &'static str,
+}
+
+const LAUNCHERS: &[Launcher] = &[
+    Launcher {
+        name: "Chrome",
+        family: Family::Chromium,
+        linux: "google-chrome",
+        macos: "Google Chrome",
+        private: "--incognito",
+    },
+    Launcher {
+        name: "Chrome Beta",
+        family: Family::Chromium,
+        linux: "google-chrome-beta",
+        macos: "Google Chrome Beta",
+        private: "--incognito",
+    },
+    Launcher {
+        name: "Chrome Dev",
+        family: Family::Chromium,
+        linux: "google-chrome-unstable",
+        macos: "Google Chrome Dev",
+        private: "--incognito",
+    },
+    Launcher {
+        name: "Chromium",
+        family: Family::Chromium,
+        linux: "chromium",
+        macos: "Chromium",
+        private: "--incognito",
+    },
+    Launcher {
+        name: "Brave",
+        family: Family::Chromium,
+        linux: "brave-browser",
+        macos: "Brave Browser",
+        private: "--incognito",
+    },
+    Launcher {
+        name: "Vivaldi",
+        family: Family::Chromium,
+        linux: "vivaldi",
+        macos: "Vivaldi",
+        private: "--incognito",
+    },
+    Launcher {
+        name: "Edge",
+        family: Family::Chromium,
+        linux: "microsoft-edge",
+        macos: "Microsoft Edge",
+        private: "--inprivate",
+    },
+    Launcher {
+        name: "Opera",
+        family: Family::Chromium,
+        linux: "opera",
+        macos: "Opera",
+        private: "--private",
+    },
+    Launcher {
+        name: "Firefox",
+        family: Family::Firefox,
+        linux: "firefox",
+        macos: "Firefox",
+        private: "--private-window",
+    },
+];
+
+/// Which browser opens the selection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Target {
+    /// The system default browser.
+    Default,
+    /// The browser and profile each entry was read from.
+    Source,
+    /// A browser by name, such as `firefox` or `brave`.
+    Browser(String),
+    /// A command line in which `{url}` is replaced by the URL, such as `firefox --new-window {url}`.
+    Command(String),
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "default" => Ok(Target::Default),
+            "source" => Ok(Target::Source),
+            name => match find_launcher(name) {
+                Some(launcher) => Ok(Target::Browser(launcher.name.to_string())),
+                None => Err(format!(
+                    "unknown browser '{}', expected default, source or one of: {}",
+                    s,
+                    LAUNCHERS
+                        .iter()
+                        .map(|l| l.name.to_ascii_lowercase())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Target {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Target> for String {
+    fn from(target: Target) -> String {
+        match target {
+            Target::Default => "default".to_string(),
+            Target::Source => "source".to_string(),
+            Target::Browser(name) => name.to_ascii_lowercase(),
+            Target::Command(command) => command,
+        }
+    }
+}
+
+/// How the selection should be opened.
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    pub target: Target,
+    /// All URLs together in one new window rather than one new tab each.
+    pub new_window: bool,
+    /// In a private (incognito) window.
+    pub private: bool,
+}
+
+/// Open `urls` in selection order, stopping at the first that fails.
+///
+/// URLs from a browser fuhl cannot start go to the default browser, with a warning if a new or
+/// private window was asked for.
+pub fn open_all(urls: &[&Url], options: &OpenOptions, warnings: &mut Vec<Error>) -> Result<()> {
+    match &options.target {
+        Target::Command(template) => {
+            for u in urls {
+                run_template(template, &u.url).map_err(|source| Error::Open {
+                    url: u.url.clone(),
+                    source,
+                })?;
+            }
+        }
+        Target::Browser(name) => {
+            let links: Vec<&str> = urls.iter().map(|u| u.url.as_str()).collect();
+            let launcher = find_launcher(name).expect("browser names are checked when parsed");
+            launch(launcher, None, &links, options)?;
+        }
+        // The default browser can't be asked for a window or private mode, so those go to the
+        // entries' own browser
+        Target::Default if !options.new_window && !options.private => open_default(urls)?,
+        Target::Default | Target::Source => {
+            for (source, group) in by_source(urls) {
+                let launcher = source.and_then(|s| find_launcher(&s.browser));
+                match launcher {
+                    Some(launcher) => {
+                        let links: Vec<&str> = group.iter().map(|u| u.url.as_str()).collect();
+                        launch(launcher, source, &links, options)?;
+                    }
+                    None => {
+                        if options.new_window || options.private {
+                            warnings.push(Error::NoWindow {
+                                browser: source
+                                    .map_or("this browser", |s| s.browser.as_str())
+                                    .to_string(),
+                            });
+                        }
+                        open_default(&group)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn open_default(urls: &[&Url]) -> Result<()> {
+    for u in urls {
+        webbrowser::open(&u.url).map_err(|source| Error::Open {
+            url: u.url.clone(),
+            source,
+        })?;
+    }
+    Ok(())
+}
+
+fn find_launcher(name: &str) -> Option<&'static Launcher> {
+    LAUNCHERS.iter().find(|l| l.name.eq_ignore_ascii_case(name))
+}
+
+/// Group URLs by the source they were most recently visited in, keeping selection order.
+fn by_source<'a>(urls: &[&'a Url]) -> Vec<(Option<&'a Source>, Vec<&'a Url>)> {
+    let mut groups: Vec<(Option<&'a Source>, Vec<&'a Url>)> = Vec::new();
+    for u in urls {
+        let source = u.sources.first();
+        match groups.iter_mut().find(|(s, _)| *s == source) {
+            Some((_, group)) => group.push(u),
+            None => groups.push((source, vec![u])),
+        }
+    }
+    groups
+}
+
+fn launch(
+    launcher: &Launcher,
+    source: Option<&Source>,
+    links: &[&str],
+    options: &OpenOptions,
+) -> Result<()> {
+    let program = if cfg!(target_os = "macos") {
+        launcher.macos
+    } else {
+        launcher.linux
+    };
+    for args in browser_args(launcher, source, links, options) {
+        spawn(program, &args).map_err(|source| Error::Launch {
+            program: program.to_string(),
+            source,
+        })?;
+    }
+    Ok(())
+}
+
+/// The browser invocations needed to open `links` in the profile of `source`, one argument list
+/// per process.
+fn browser_args(
+    launcher: &Launcher,
+    source: Option<&Source>,
+    links: &[&str],
+    options: &OpenOptions,
+) -> Vec<Vec<String>> {
+    match launcher.family {
+        Family::Chromium => {
+            let mut args = Vec::new();
+            // Chromium names profiles by their directory under the user data directory
+            if let Some(source) = source.filter(|s| !s.profile.is_empty()) {
+                args.push(format!("--profile-directory={}", source.profile));
+            }
+            if options.private {
+                args.push(launcher.private.to_string());
+            }
+            if options.new_window {
+                args.push("--new-window".to_string());
+            }
+            args.extend(links.iter().map(|l| l.to_string()));
+            vec![args]
+        }
+        Family::Firefox => {
+            // `-P` takes the name in profiles.ini, which databases given by path don't have
+            let dir = source
+                .and_then(|s| s.path.parent())
+                .filter(|dir| !dir.as_os_str().is_empty());
+            links
+                .iter()
+                .enumerate()
+                .map(|(i, link)| {
+                    let mut args = Vec::new();
+                    if let Some(dir) = dir {
+                        args.extend(["--profile".to_string(), dir.to_string_lossy().into_owned()]);
+                    }
+                    let flag = if options.private {
+                        launcher.private
+                    } else if options.new_window && i == 0 {
+                        "--new-window"
+                    } else {
+                        "--new-tab"
+                    };
+                    args.extend([flag.to_string(), link.to_string()]);
+                    args
+                })
+                .collect()
+        }
+    }
+}
+
+/// Run a `--open-with` command line for one URL; without `{url}` the URL is appended.
+fn run_template(template: &str, url: &str) -> std::io::Result<()> {
+    let words = shlex::split(template).ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "unbalanced quotes")
+    })?;
+    let mut words: Vec<String> = if template.contains("{url}") {
+        words.iter().map(|w| w.replace("{url}", url)).collect()
+    } else {
+        words.into_iter().chain([url.to_string()]).collect()
+    };
+    if words.is_empty() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "empty command",
+        ));
+    }
+    let program = words.remove(0);
+    Command::new(program)
+        .args(words)
+        .stdin(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+/// Start a browser without waiting for it; it may keep running long after fuhl exits.
+fn spawn(program: &str, args: &[String]) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut open = Command::new("open");
+        // Without -n a running browser is reused rather than started a second time
+        open.args(["-a", program, "--args"]);
+        open
+    } else {
+        Command::new(program)
+    };
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(browser: &str, source: Option<&Source>, options: &OpenOptions) -> Vec<Vec<String>> {
+        let launcher = find_launcher(browser).unwrap();
+        browser_args(
+            launcher,
+            source,
+            &["https://a.example/", "https://b.example/"],
+            options,
+        )
+    }
+
+    fn options(new_window: bool, private: bool) -> OpenOptions {
+        OpenOptions {
+            target: Target::Source,
+            new_window,
+            private,
+        }
+    }
+
+    #[test]
+    fn groups_urls_by_their_latest_source() {
+        let source = |browser: &str| Source {
+            browser: browser.to_string(),
+            profile: String::new(),
+            path: Default::default(),
+        };
+        let url = |url: &str, sources: Vec<Source>| Url {
+            url: url.to_string(),
+            sources,
+            ..Url::default()
+        };
+        let urls = [
+            url("https://a.example/", vec![source("Chrome")]),
+            url(
+                "https://b.example/",
+                vec![source("Firefox"), source("Chrome")],
+            ),
+            url("https://c.example/", vec![]),
+            url("https://d.example/", vec![source("Chrome")]),
+        ];
+        let urls: Vec<&Url> = urls.iter().collect();
+        let groups: Vec<(Option<&str>, Vec<&str>)> = by_source(&urls)
+            .into_iter()
+            .map(|(s, group)| {
+                let links = group.iter().map(|u| u.url.as_str()).collect();
+                (s.map(|s| s.browser.as_str()), links)
+            })
+            .collect();
+        assert_eq!(
+            groups,
+            [
+                (
+                    Some("Chrome"),
+                    vec!["https://a.example/", "https://d.example/"]
+                ),
+                (Some("Firefox"), vec!["https://b.example/"]),
+                (None, vec!["https://c.example/"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn open_with_commands_must_be_runnable() {
+        let invalid = |template: &str| {
+            run_template(template, "https://a.example/")
+                .unwrap_err()
+                .kind()
+        };
+        assert_eq!(
+            invalid("open 'unbalanced"),
+            std::io::ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            invalid("/nonexistent/fuhl-opener {url}"),
+            std::io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            invalid("/nonexistent/fuhl-opener"),
+            std::io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn chromium_opens_every_link_in_one_process() {
+        let source = Source {
+            browser: "Chrome".to_string(),
+            profile: "Profile 1".to_string(),
+            path: "/home/me/.config/google-chrome/Profile 1/History".into(),
+        };
+        assert_eq!(
+            args("chrome", Some(&source), &options(true, false)),
+            [[
+                "--profile-directory=Profile 1",
+                "--new-window",
+                "https://a.example/",
+                "https://b.example/"
+            ]]
+        );
+        assert_eq!(
+            args("edge", None, &options(false, true)),
+            [["--inprivate", "https://a.example/", "https://b.example/"]]
+        );
+    }
+
+    #[test]
+    fn firefox_is_given_the_profile_directory() {
+        let source = Source {
+            browser: "Firefox".to_string(),
+            profile: "default-release".to_string(),
+            path: "/home/me/.mozilla/firefox/x1y2.default-release/places.sqlite".into(),
+        };
+        let dir = "/home/me/.mozilla/firefox/x1y2.default-release";
+        assert_eq!(
+            args("firefox", Some(&source), &options(true, false)),
+            [
+                ["--profile", dir, "--new-window", "https://a.example/"],
+                ["--profile", dir, "--new-tab", "https://b.example/"]
+            ]
+        );
+        assert_eq!(
+            args("firefox", None, &options(true, true)),
+            [
+                ["--private-window", "https://a.example/"],
+                ["--private-window", "https://b.example/"]
+            ]
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use crate::error::{Error, Result};
+use crate::history::Url;
+use crate::index;
+use crate::time::Timestamp;
+use regex::{Captures, Regex};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{LazyLock, mpsc};
+
+/// Longer page text is cut off when it is stored.
+pub const MAX_TEXT_CHARS: usize = 50_000;
+
+/// How many pages [`fetch_all`] downloads at once.
+pub const FETCH_JOBS: usize = 8;
+
+pub(crate) const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS page_text (
+    url TEXT PRIMARY KEY,
+    text TEXT NOT NULL,
+    -- When the text was stored, in WebKit microseconds
+    stored INTEGER NOT NULL
+);
+";
+
+/// Readable text of chosen pages, kept in fuhl's store next to the index so that pages can be
+/// found by what they say rather than only by their title and URL.
+pub struct PageText {
+    conn: Connection,
+    path: PathBuf,
+}
+
+impl PageText {
+    /// Open or create the page text store in the database at `path`.
+    pub fn open(path: &Path) -> Result<PageText> {
+        Ok(PageText {
+            conn: index::open_store(path)?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn sqlite(&self, source: rusqlite::Error) -> Error {
+        Error::Sqlite {
+            path: self.path.clone(),
+            source,
+        }
+    }
+
+    /// Whether text is stored for `url`.
+    pub fn contains(&self, url: &str) -> Result<bool> {
+        self.conn
+            .query_row("SELECT 1 FROM page_text WHERE url = ?1", [url], |_| Ok(()))
+            .optional()
+            .map(|found| found.is_some())
+            .map_err(|e| self.sqlite(e))
+    }
+
+    /// Store `text` for `url`, replacing what was stored before.
+    pub fn store(&self, url: &str, text: &str) -> Result<()> {
+        let text: String = text.chars().take(MAX_TEXT_CHARS).collect();
+        self.conn
+            .execute(
+                "INSERT INTO page_text (url, text, stored) VALUES (?1, ?2, ?3)
+                 ON CONFLICT (url) DO UPDATE SET text = ?2, stored = ?3",
+                params![url, text, Timestamp::now().webkit_micros()],
+            )
+            .map_err(|e| self.sqlite(e))?;
+        Ok(())
+    }
+
+    /// Set [`Url::text`] on every row whose page has text stored.
+    pub fn attach(&self, urls: &mut [Url]) -> Result<()> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT url, text FROM page_text")
+            .map_err(|e| self.sqlite(e))?;
+        let texts = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<HashMap<String, String>>>())
+            .map_err(|e| self.sqlite(e))?;
+        for u in urls {
+            if let Some(text) = texts.get(&u.url) {
+                u.text = Some(text.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete all stored text, returning how many pages had some.
+    pub fn clear(&self) -> Result<usize> {
+        self.conn
+            .execute("DELETE FROM page_text", [])
+            .map_err(|e| self.sqlite(e))
+    }
+}
+
+/// Download `url` with `curl` and reduce it to its readable text.
+pub fn fetch(url: &str) -> Result<String> {
+    let error = |message: String| Error::Fetch {
+        url: url.to_string(),
+        message,
+    };
+    if !(url.starts_with("http://") || url.starts_with("https://")) {
+        return Err(error(
+            "only http and https pages can be fetched".to_string(),
+        ));
+    }
+    let output = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--proto", "=http,https", "--max-time", "20"])
+        .args(["--max-filesize", "5000000"])
+        .args(["--user-agent", concat!("fuhl/", env!("CARGO_PKG_VERSION"))])
+        .arg("--url")
+        .arg(url)
+        .output()
+        .map_err(|e| error(format!("could not run curl: {}", e)))?;
+    if !output.status.success() {
+        return Err(error(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(readable_text(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Elements whose content is never shown: comments, the head, scripts, styles and the like.
+static HIDDEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?is)<!--.*?-->|<head\b.*?</head>|<script\b.*?</script>|<style\b.*?</style>|<noscript\b.*?</noscript>|<template\b.*?</template>|<svg\b.*?</svg>",
+    )
+    .expect("hidden element pattern is valid")
+});
+
+static TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<[^>]*>").expect("tag pattern is valid"));
+
+static ENTITY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"&(#[xX][0-9a-fA-F]+|#[0-9]+|[a-zA-Z]+);").expect("entity pattern is valid")
+});
+
+/// Download each of `urls` with [`fetch`], [`FETCH_JOBS`] at a time, handing every result to
+/// `fetched` as it arrives. Stops at the first error `fetched` returns, once the downloads under
+/// way have finished.
+pub fn fetch_all(
+    urls: &[String],
+    mut fetched: impl FnMut(&str, Result<String>) -> Result<()>,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for _ in 0..FETCH_JOBS.min(urls.len()) {
+            let tx = tx.clone();
+            let (next, stop) = (&next, &stop);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let Some(url) = urls.get(next.fetch_add(1, Ordering::Relaxed)) else {
+                        break;
+                    };
+                    if tx.send((url, fetch(url))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        // Only the workers hold senders now, so the loop ends when they are done
+        drop(tx);
+        for (url, text) in rx {
+            if let Err(e) = fetched(url, text) {
+                stop.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+        Ok(())
+    })
+}
+
+/// The visible text of an HTML page on one line: no markup, scripts or styles, with entities
+/// decoded and whitespace collapsed. Plain text passes through with its whitespace collapsed.
+pub fn readable_text(html: &str) -> String {
+    let text = HIDDEN.replace_all(html, " ");
+    let text = TAG.replace_all(&text, " ");
+    let text = ENTITY.replace_all(&text, |caps: &Captures| {
+        let name = &caps[1];
+        let decoded = if let Some(hex) = name.strip_prefix("#x").or(name.strip_prefix("#X")) {
+            u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+        } else if let Some(decimal) = name.strip_prefix('#') {
+            decimal.parse().ok().and_then(char::from_u32)
+        } else {
+            match name {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ => None,
+            }
+        };
+        match decoded {
+            Some(c) => c.to_string(),
+            None => caps[0].to_string(),
+        }
+    });
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_only_visible_text() {
+        let html = r#"<!DOCTYPE html>
+            <html><head><title>Dashboard</title><style>p { color: red }</style></head>
+            <body><script>var x = "<p>";</script>
+            <!-- nav --><h1>Payments&nbsp;latency</h1>
+            <p class="x">p99 &lt; 200ms &amp; errors &#8804; 1&#x25;</p>
+            </body></html>"#;
+        assert_eq!(
+            readable_text(html),
+            "Payments latency p99 < 200ms & errors ≤ 1%"
+        );
+        assert_eq!(
+            readable_text("plain\n\n text &bogus;"),
+            "plain text &bogus;"
+        );
+    }
+
+    #[test]
+    fn fetches_every_page_once() {
+        let urls: Vec<String> = (0..20).map(|i| format!("ftp://{}.example/", i)).collect();
+        let mut failed = Vec::new();
+        fetch_all(&urls, |url, text| {
+            assert!(matches!(text, Err(Error::Fetch { .. })));
+            failed.push(url.to_string());
+            Ok(())
+        })
+        .unwrap();
+        failed.sort_by_key(|url| urls.iter().position(|u| u == url));
+        assert_eq!(failed, urls);
+
+        let mut seen = 0;
+        let stopped = fetch_all(&urls, |_, _| {
+            seen += 1;
+            Err(Error::Custom("stop".into()))
+        });
+        assert!(stopped.is_err());
+        assert_eq!(seen, 1);
+    }
+
+    #[test]
+    fn stores_and_attaches_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = PageText::open(&dir.path().join("index.sqlite")).unwrap();
+        store.store("https://a.example/", "first").unwrap();
+        store.store("https://a.example/", "second").unwrap();
+        assert!(store.contains("https://a.example/").unwrap());
+        assert!(!store.contains("https://b.example/").unwrap());
+
+        let mut urls = vec![
+            Url {
+                url: "https://a.example/".to_string(),
+                ..Url::default()
+            },
+            Url {
+                url: "https://b.example/".to_string(),
+                ..Url::default()
+            },
+        ];
+        store.attach(&mut urls).unwrap();
+        assert_eq!(urls[0].text.as_deref(), Some("second"));
+        assert_eq!(urls[1].text, None);
+        assert_eq!(store.clear().unwrap(), 1);
+    }
+}
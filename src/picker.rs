@@ -0,0 +1,194 @@
+use crate::action::Action;
+use crate::config::Config;
+use crate::display;
+use crate::error::{Error, Result};
+use crate::headless::PageTextMatcher;
+use crate::history::Url;
+use crate::time::Timestamp;
+use skim::prelude::*;
+use skim::reader::CommandCollector;
+
+/// What to start the picker with, beyond the [`Config`].
+#[derive(Debug, Clone, Default)]
+pub struct PickOptions {
+    pub query: Option<String>,
+    /// Accept the only match without showing the picker.
+    pub select_1: bool,
+    /// Exit straight away when nothing matches.
+    pub exit_0: bool,
+}
+
+/// The chosen rows, as indexes into the list in the order they were picked, and what to do.
+#[derive(Debug)]
+pub struct Selection {
+    pub indexes: Vec<usize>,
+    pub action: Action,
+}
+
+/// One row of the picker. Its output is the row's index into the ranked list.
+struct Entry {
+    urls: Arc<Vec<Url>>,
+    index: usize,
+    line: String,
+    /// Characters of `line` to highlight, when matching was done outside skim.
+    highlights: Option<Vec<usize>>,
+}
+
+impl Entry {
+    fn new(urls: &Arc<Vec<Url>>, index: usize, highlights: Option<Vec<usize>>) -> Entry {
+        Entry {
+            urls: Arc::clone(urls),
+            index,
+            line: display::line(&urls[index]),
+            highlights,
+        }
+    }
+}
+
+impl SkimItem for Entry {
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.line)
+    }
+
+    fn display<'a>(&'a self, context: DisplayContext<'a>) -> AnsiString<'a> {
+        let Some(highlights) = &self.highlights else {
+            return AnsiString::from(context);
+        };
+        let fragments = highlights
+            .iter()
+            .map(|&i| (context.highlight_attr, (i as u32, i as u32 + 1)))
+            .collect();
+        AnsiString::new_str(&self.line, fragments)
+    }
+
+    fn preview(&self, _context: PreviewContext) -> ItemPreview {
+        ItemPreview::Text(display::preview(&self.urls[self.index], Timestamp::now()))
+    }
+
+    fn output(&self) -> Cow<'_, str> {
+        Cow::Owned(self.index.to_string())
+    }
+}
+
+/// Feed every row to skim, in ranked order.
+fn items(urls: &Arc<Vec<Url>>) -> SkimItemReceiver {
+    let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+    for index in 0..urls.len() {
+        // The receiver is held right here, so sending cannot fail
+        let _ = tx.send(Arc::new(Entry::new(urls, index, None)));
+    }
+    rx
+}
+
+/// Matches rows against page text as well as their lines, see [`PageTextMatcher`].
+///
+/// Skim can only match the text of an item one way, so with page text it runs in interactive
+/// mode and hands every change of the query to [`CommandCollector::invoke`], which sends back the
+/// matching rows: matches in the line first, best first, then matches in the text alone.
+struct PageTextCollector {
+    urls: Arc<Vec<Url>>,
+}
+
+impl CommandCollector for PageTextCollector {
+    fn invoke(
+        &mut self,
+        query: &str,
+        components_to_stop: Arc<AtomicUsize>,
+    ) -> (SkimItemReceiver, Sender<i32>) {
+        let (tx, rx): (SkimItemSender, SkimItemReceiver) = unbounded();
+        let (tx_interrupt, rx_interrupt) = bounded(1);
+        let urls = Arc::clone(&self.urls);
+        let query = query.to_string();
+        components_to_stop.fetch_add(1, Ordering::SeqCst);
+        std::thread::spawn(move || {
+            let matcher = PageTextMatcher::new(&query);
+            let mut found = Vec::new();
+            for (index, u) in urls.iter().enumerate() {
+                // Skim interrupts a search that a newer query replaced
+                if index % 1000 == 0 && rx_interrupt.try_recv().is_ok() {
+                    break;
+                }
+                if let Some((rank, highlights)) = matcher.match_url(u) {
+                    found.push((rank, index, highlights));
+                }
+            }
+            found.sort_by_key(|&(rank, index, _)| (rank, index));
+            for (_, index, highlights) in found {
+                if tx
+                    .send(Arc::new(Entry::new(&urls, index, Some(highlights))))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            components_to_stop.fetch_sub(1, Ordering::SeqCst);
+        });
+        (rx, tx_interrupt)
+    }
+}
+
+/// Show the picker over `urls` and return what was chosen.
+pub fn pick(urls: &Arc<Vec<Url>>, config: &Config, options: &PickOptions) -> Result<Selection> {
+    // Configure skim options: reasonable height, a preview pane, starting from the search terms
+    let mut builder = SkimOptionsBuilder::default();
+    builder
+        .height(config.picker.height.clone())
+        .preview(Some(String::new()))
+        .preview_window(config.picker.preview_window.clone())
+        .multi(config.picker.multi)
+        .select_1(options.select_1)
+        .exit_0(options.exit_0)
+        .expect(config.keys.keys().cloned().collect());
+    let source = if config.picker.page_text {
+        // The collector is given the query and does the matching, in its own order
+        let collector = PageTextCollector {
+            urls: Arc::clone(urls),
+        };
+        builder
+            .interactive(true)
+            .cmd(Some("{}".to_string()))
+            .cmd_query(options.query.clone())
+            .cmd_prompt("> ".to_string())
+            .no_sort(true)
+            .cmd_collector(Rc::new(RefCell::new(collector)) as Rc<RefCell<dyn CommandCollector>>);
+        None
+    } else {
+        builder.query(options.query.clone());
+        Some(items(urls))
+    };
+    let skim_options = builder.build().map_err(|e| Error::Skim(e.to_string()))?;
+
+    let out = Skim::run_with(&skim_options, source)
+        .ok_or_else(|| Error::Skim("could not start the picker".to_string()))?;
+    if out.is_abort {
+        // --exit-0 aborts without a key press
+        return Err(if out.final_key == Key::Null {
+            Error::NoMatches
+        } else {
+            Error::NoSelection
+        });
+    }
+
+    // Parse the selected lines to get the indexes, keeping the order they were picked in
+    let indexes: Vec<usize> = out
+        .selected_items
+        .iter()
+        .filter_map(|item| item.output().parse().ok())
+        .filter(|&index| index < urls.len())
+        .collect();
+    if indexes.is_empty() {
+        // Accepting with nothing highlighted means nothing matched the query
+        return Err(Error::NoMatches);
+    }
+
+    // Enter runs the configured action, the expected keys their own
+    let key = match out.final_event {
+        Event::EvActAccept(key) => key,
+        _ => None,
+    };
+    let action = key
+        .as_deref()
+        .and_then(|key| config.action_for_key(key))
+        .unwrap_or(config.open.action);
+    Ok(Selection { indexes, action })
+}
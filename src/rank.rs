@@ -0,0 +1,276 @@
+use crate::history::{Transition, Url};
+use crate::time::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// How the picker orders entries. Bookmarks always come first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    /// Frequency and recency combined, see [`frecency`].
+    #[default]
+    Frecency,
+    /// Most recently visited first, then most visited.
+    Recent,
+}
+
+impl std::str::FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "frecency" => Ok(SortOrder::Frecency),
+            "recent" => Ok(SortOrder::Recent),
+            _ => Err(format!(
+                "unknown sort order '{}', expected frecency or recent",
+                s
+            )),
+        }
+    }
+}
+
+/// Tuning for [`frecency`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    /// Days after which a visit counts half as much.
+    pub half_life_days: f64,
+    pub typed: f64,
+    pub bookmark: f64,
+    pub link: f64,
+    pub redirect: f64,
+    pub reload: f64,
+    pub other: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights {
+            half_life_days: 14.0,
+            typed: 2.0,
+            bookmark: 1.4,
+            link: 1.0,
+            redirect: 0.25,
+            reload: 0.0,
+            other: 0.5,
+        }
+    }
+}
+
+impl Weights {
+    /// Check that every weight is a non-negative number and the half-life is positive.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.half_life_days > 0.0 && self.half_life_days.is_finite()) {
+            return Err(format!(
+                "rank.weights.half_life_days must be positive, got {}",
+                self.half_life_days
+            ));
+        }
+        let weights = [
+            ("typed", self.typed),
+            ("bookmark", self.bookmark),
+            ("link", self.link),
+            ("redirect", self.redirect),
+            ("reload", self.reload),
+            ("other", self.other),
+        ];
+        for (name, weight) in weights {
+            if !(weight >= 0.0 && weight.is_finite()) {
+                return Err(format!(
+                    "rank.weights.{} must not be negative, got {}",
+                    name, weight
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn transition(&self, transition: Transition) -> f64 {
+        match transition {
+            Transition::Typed => self.typed,
+            Transition::Bookmark => self.bookmark,
+            Transition::Link => self.link,
+            Transition::Redirect => self.redirect,
+            Transition::Reload => self.reload,
+            Transition::Other => self.other,
+        }
+    }
+}
+
+/// Score a URL by how often and how recently it was visited, and how it was reached.
+///
+/// Each sampled visit is worth its transition weight, halved for every `half_life_days` of age.
+/// The average over the samples is scaled by the total visit count, with typed visits counted
+/// twice. Rows without visit samples are scored from their last visit alone.
+pub fn frecency(url: &Url, now: Timestamp, weights: &Weights) -> f64 {
+    let age_weight = |time: Timestamp| {
+        let age_days = time.days_until(now);
+        0.5_f64.powf(age_days / weights.half_life_days)
+    };
+
+    let sample = if url.visits.is_empty() {
+        let transition = if url.typed_count > 0 {
+            Transition::Typed
+        } else {
+            Transition::Link
+        };
+        weights.transition(transition) * age_weight(url.last_visit_time)
+    } else {
+        let total: f64 = url
+            .visits
+            .iter()
+            .map(|v| weights.transition(v.transition) * age_weight(v.time))
+            .sum();
+        total / url.visits.len() as f64
+    };
+
+    (url.visit_count + url.typed_count).max(1) as f64 * sample
+}
+
+/// Order rows for the picker: bookmarks first, then by the chosen order.
+pub fn sort(urls: &mut [Url], order: SortOrder, weights: &Weights) {
+    match order {
+        SortOrder::Frecency => {
+            let now = Timestamp::now();
+            let scores: Vec<f64> = urls.iter().map(|u| frecency(u, now, weights)).collect();
+            let mut order: Vec<usize> = (0..urls.len()).collect();
+            order.sort_by(|&a, &b| {
+                (urls[a].bookmark.is_none())
+                    .cmp(&urls[b].bookmark.is_none())
+                    .then(scores[b].total_cmp(&scores[a]))
+            });
+            let sorted: Vec<Url> = order
+                .iter()
+                .map(|&i| std::mem::take(&mut urls[i]))
+                .collect();
+            for (slot, u) in urls.iter_mut().zip(sorted) {
+                *slot = u;
+            }
+        }
+        SortOrder::Recent => urls.sort_by(|a, b| {
+            b.bookmark
+                .is_some()
+                .cmp(&a.bookmark.is_some())
+                .then(b.last_visit_time.cmp(&a.last_visit_time))
+                .then(b.visit_count.cmp(&a.visit_count))
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Visit;
+    use std::time::Duration;
+
+    const NOW: Timestamp = Timestamp::from_webkit_micros(13_400_000_000_000_000);
+
+    fn days_ago(days: u64) -> Timestamp {
+        NOW - Duration::from_secs(days * 86_400)
+    }
+
+    fn url(visit_count: i64, typed_count: i64, visits: Vec<Visit>) -> Url {
+        Url {
+            id: 1,
+            url: "https://example.com/".to_string(),
+            title: String::new(),
+            visit_count,
+            typed_count,
+            last_visit_time: visits.first().map_or(NOW, |v| v.time),
+            hidden: 0,
+            sources: Vec::new(),
+            bookmark: None,
+            visits,
+            timeline: Default::default(),
+            visit: None,
+            text: None,
+            search: None,
+            tab: None,
+        }
+    }
+
+    fn visits(transition: Transition, days: &[u64]) -> Vec<Visit> {
+        days.iter()
+            .map(|&d| Visit {
+                time: days_ago(d),
+                transition,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn visit_halves_after_half_life() {
+        let weights = Weights::default();
+        let fresh = frecency(&url(1, 0, visits(Transition::Link, &[0])), NOW, &weights);
+        let old = frecency(&url(1, 0, visits(Transition::Link, &[14])), NOW, &weights);
+        assert!((fresh - 1.0).abs() < 1e-9);
+        assert!((old - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn daily_typed_dashboard_beats_single_recent_visit() {
+        let weights = Weights::default();
+        let dashboard = url(
+            200,
+            150,
+            visits(Transition::Typed, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10]),
+        );
+        let once = url(1, 0, visits(Transition::Link, &[0]));
+        assert!(frecency(&dashboard, NOW, &weights) > frecency(&once, NOW, &weights));
+    }
+
+    #[test]
+    fn sorts_by_score_whatever_its_sign() {
+        let weights = Weights {
+            link: -1.0,
+            ..Weights::default()
+        };
+        let mut urls = vec![
+            url(1, 0, visits(Transition::Link, &[0])),
+            url(1, 0, visits(Transition::Reload, &[0])),
+            url(1, 0, visits(Transition::Typed, &[0])),
+        ];
+        for (i, u) in urls.iter_mut().enumerate() {
+            u.url = i.to_string();
+        }
+        sort(&mut urls, SortOrder::Frecency, &weights);
+        let order: Vec<&str> = urls.iter().map(|u| u.url.as_str()).collect();
+        assert_eq!(order, ["2", "1", "0"]);
+    }
+
+    #[test]
+    fn transition_weights_apply_per_visit() {
+        let weights = Weights::default();
+        let typed = frecency(&url(1, 0, visits(Transition::Typed, &[0])), NOW, &weights);
+        let redirect = frecency(
+            &url(1, 0, visits(Transition::Redirect, &[0])),
+            NOW,
+            &weights,
+        );
+        let reload = frecency(&url(1, 0, visits(Transition::Reload, &[0])), NOW, &weights);
+        assert!(typed > redirect);
+        assert_eq!(reload, 0.0);
+    }
+
+    #[test]
+    fn scores_last_visit_without_samples() {
+        let weights = Weights::default();
+        let mut typed = url(3, 1, Vec::new());
+        typed.last_visit_time = days_ago(14);
+        // (3 + 1) visits * typed weight 2.0 * one half-life of decay
+        assert!((frecency(&typed, NOW, &weights) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sort_keeps_bookmarks_first() {
+        let weights = Weights::default();
+        let mut bookmarked = url(0, 0, Vec::new());
+        bookmarked.url = "https://bookmarked.example/".to_string();
+        bookmarked.bookmark = Some("Bookmarks bar".to_string());
+        let mut urls = vec![url(50, 10, visits(Transition::Typed, &[0])), bookmarked];
+
+        sort(&mut urls, SortOrder::Frecency, &weights);
+        assert_eq!(urls[0].url, "https://bookmarked.example/");
+        sort(&mut urls, SortOrder::Recent, &weights);
+        assert_eq!(urls[0].url, "https://bookmarked.example/");
+    }
+}
@@ -0,0 +1,151 @@
+use crate::history::{Timeline, Transition, Url, VisitDetail, collect_rows};
+use crate::time::Timestamp;
+use rusqlite::Connection;
+
+/// Read `history_items` and their visits from a Safari `History.db` database.
+///
+/// Safari keeps titles on visits rather than items, so the title of the latest visit is used.
+/// It does not record typed navigations, and its visit times are seconds since 2001-01-01.
+pub fn read_urls(
+    conn: &Connection,
+    since: Timestamp,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    // With a single MAX() in an aggregate, SQLite takes bare columns from the row holding the
+    // maximum, so the first visit comes from a subquery
+    let mut stmt = conn.prepare(
+        "SELECT i.id, i.url, COALESCE(v.title, ''), i.visit_count, MAX(v.visit_time),
+                (SELECT MIN(visit_time) FROM history_visits WHERE history_item = i.id)
+         FROM history_items i
+         JOIN history_visits v ON v.history_item = i.id
+         GROUP BY i.id
+         HAVING MAX(v.visit_time) >= ?1
+         ORDER BY MAX(v.visit_time) DESC, i.visit_count DESC",
+    )?;
+
+    let url_iter = stmt.query_map([since.core_data_secs()], |row| {
+        Ok(Url {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            title: row.get(2)?,
+            visit_count: row.get(3)?,
+            typed_count: 0,
+            last_visit_time: Timestamp::from_core_data_secs(row.get(4)?),
+            hidden: 0,
+            sources: Vec::new(),
+            bookmark: None,
+            visits: Vec::new(),
+            timeline: Timeline {
+                first_visit_time: Some(Timestamp::from_core_data_secs(row.get(5)?)),
+                ..Timeline::default()
+            },
+            visit: None,
+            text: None,
+            search: None,
+            tab: None,
+        })
+    })?;
+
+    Ok(collect_rows(url_iter, skipped))
+}
+
+/// Read every row of `history_visits`, newest first.
+///
+/// Safari records no transition types; a visit that a redirect led to is marked as one, with
+/// the page that redirected as its referrer.
+pub fn read_visit_log(
+    conn: &Connection,
+    skipped: &mut Vec<rusqlite::Error>,
+) -> rusqlite::Result<Vec<Url>> {
+    let mut stmt = conn.prepare(
+        "SELECT i.id, i.url, COALESCE(v.title, ''), v.visit_time, r.url
+         FROM history_visits v
+         JOIN history_items i ON i.id = v.history_item
+         LEFT JOIN history_visits f ON f.id = v.redirect_source
+         LEFT JOIN history_items r ON r.id = f.history_item
+         ORDER BY v.visit_time DESC",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let referrer: Option<String> = row.get(4)?;
+        let transition = if referrer.is_some() {
+            Transition::Redirect
+        } else {
+            Transition::Other
+        };
+        Ok(Url::visit(
+            row.get(0)?,
+            row.get(1)?,
+            row.get(2)?,
+            Timestamp::from_core_data_secs(row.get(3)?),
+            0,
+            VisitDetail {
+                transition,
+                referrer,
+            },
+        ))
+    })?;
+    Ok(collect_rows(rows, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Browser;
+
+    fn fixture() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE history_items (id INTEGER PRIMARY KEY, url TEXT NOT NULL UNIQUE,
+                 domain_expansion TEXT NULL, visit_count INTEGER NOT NULL);
+             CREATE TABLE history_visits (id INTEGER PRIMARY KEY, history_item INTEGER NOT NULL,
+                 visit_time REAL NOT NULL, title TEXT NULL, redirect_source INTEGER NULL);
+             INSERT INTO history_items VALUES (1, 'https://example.com/', 'example', 2);
+             INSERT INTO history_items VALUES (2, 'https://news.example.org/', NULL, 1);
+             INSERT INTO history_visits VALUES (1, 1, 700000000.0, 'Old title', NULL);
+             INSERT INTO history_visits VALUES (2, 1, 800000000.5, 'Example Domain', NULL);
+             INSERT INTO history_visits VALUES (3, 2, 750000000.0, NULL, 1);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn detects_safari_schema() {
+        assert_eq!(Browser::detect(&fixture()), Some(Browser::Safari));
+    }
+
+    #[test]
+    fn reads_latest_visit_title_and_time() {
+        let urls = read_urls(&fixture(), Timestamp::default(), &mut Vec::new()).unwrap();
+        assert_eq!(urls.len(), 2);
+
+        let first = &urls[0];
+        assert_eq!(first.url, "https://example.com/");
+        assert_eq!(first.title, "Example Domain");
+        assert_eq!(first.visit_count, 2);
+        assert_eq!(first.typed_count, 0);
+        // 2001-01-01 + 800000000.5s == 2026-05-09T06:13:20.5Z == Unix 1778307200.5
+        assert_eq!(
+            first.last_visit_time,
+            Timestamp::from_unix_micros(1_778_307_200_500_000)
+        );
+
+        assert_eq!(urls[1].title, "");
+    }
+
+    #[test]
+    fn lists_each_visit_with_redirect_source() {
+        let visits = read_visit_log(&fixture(), &mut Vec::new()).unwrap();
+        let times: Vec<Timestamp> = visits.iter().map(|v| v.last_visit_time).collect();
+        assert_eq!(
+            times,
+            [800_000_000.5, 750_000_000.0, 700_000_000.0].map(Timestamp::from_core_data_secs)
+        );
+
+        let redirected = visits[1].visit.as_ref().unwrap();
+        assert_eq!(redirected.transition, Transition::Redirect);
+        assert_eq!(redirected.referrer.as_deref(), Some("https://example.com/"));
+        assert_eq!(visits[0].visit.as_ref().unwrap().referrer, None);
+        assert_eq!(visits[2].title, "Old title");
+    }
+}
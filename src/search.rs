@@ -0,0 +1,321 @@
+use crate::error::{Error, Result};
+use crate::history::Url;
+use crate::index::Index;
+use rusqlite::types::FromSql;
+use rusqlite::{Connection, params_from_iter};
+use std::str::FromStr;
+
+/// A full-text query over page titles, URLs and stored page text, run with SQLite's FTS5.
+///
+/// Terms are words or `"quoted phrases"`, all of which must match. A term can be limited to one
+/// field with `title:`, `url:` or `text:`, matched as a prefix with a trailing `*`, or excluded with a
+/// leading `-`. `OR` between terms matches either side, and binds more loosely than the implicit
+/// AND: `title:"incident review" -staging` or `grafana OR kibana dash*`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    /// Groups of terms that must all match; a row matches if any group does.
+    any: Vec<Vec<Term>>,
+    /// Terms no matching row may match.
+    not: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+    field: Option<&'static str>,
+    text: String,
+    prefix: bool,
+}
+
+/// The columns of the search table, which are also the field qualifiers.
+const FIELDS: &[&str] = &["title", "url", "text"];
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut query = Query {
+            any: Vec::new(),
+            not: Vec::new(),
+        };
+        let mut group: Vec<Term> = Vec::new();
+        // Whether the previous token was OR, or an excluded term, neither of which OR may follow
+        let mut after_or = false;
+        let mut after_not = false;
+        let mut rest = s.trim_start();
+
+        while !rest.is_empty() {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            if &rest[..end] == "OR" {
+                if group.is_empty() || after_or || after_not {
+                    return Err("OR needs a term on both sides".to_string());
+                }
+                query.any.push(std::mem::take(&mut group));
+                after_or = true;
+                rest = rest[end..].trim_start();
+                continue;
+            }
+
+            let negated = rest.len() > 1 && rest.starts_with('-');
+            if negated {
+                rest = &rest[1..];
+            }
+            let field = FIELDS.iter().copied().find(|f| {
+                rest.get(..f.len() + 1)
+                    .is_some_and(|p| p.eq_ignore_ascii_case(&format!("{}:", f)))
+            });
+            if let Some(f) = field {
+                rest = &rest[f.len() + 1..];
+            }
+
+            let (mut text, mut tail) = if let Some(quoted) = rest.strip_prefix('"') {
+                let close = quoted
+                    .find('"')
+                    .ok_or_else(|| format!("Unterminated phrase: \"{}", quoted))?;
+                (&quoted[..close], &quoted[close + 1..])
+            } else {
+                let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            };
+            let mut prefix = false;
+            if let Some(after) = tail.strip_prefix('*') {
+                prefix = true;
+                tail = after;
+            } else if let Some(word) = text.strip_suffix('*') {
+                prefix = true;
+                text = word;
+            }
+            if text.trim().is_empty() {
+                return Err(format!("Empty search term in `{}`", s));
+            }
+            if !tail.is_empty() && !tail.starts_with(char::is_whitespace) {
+                return Err(format!("Expected a space after `{}`", text));
+            }
+
+            let term = Term {
+                field,
+                text: text.to_string(),
+                prefix,
+            };
+            if negated {
+                if after_or {
+                    return Err("OR needs a term on both sides".to_string());
+                }
+                query.not.push(term);
+            } else {
+                group.push(term);
+            }
+            after_or = false;
+            after_not = negated;
+            rest = tail.trim_start();
+        }
+
+        if after_or {
+            return Err("OR needs a term on both sides".to_string());
+        }
+        if !group.is_empty() {
+            query.any.push(group);
+        }
+        if query.any.is_empty() && query.not.is_empty() {
+            return Err("Empty search query".to_string());
+        }
+        Ok(query)
+    }
+}
+
+impl Term {
+    /// The term in FTS5 query syntax, quoted so that any punctuation is taken literally.
+    fn to_fts(&self) -> String {
+        let mut fts = String::new();
+        if let Some(field) = self.field {
+            fts.push_str(field);
+            fts.push_str(" : ");
+        }
+        fts.push('"');
+        fts.push_str(&self.text.replace('"', "\"\""));
+        fts.push('"');
+        if self.prefix {
+            fts.push_str(" *");
+        }
+        fts
+    }
+}
+
+impl Query {
+    /// The rows that must match, in FTS5 query syntax, if any are required.
+    fn positive(&self) -> Option<String> {
+        if self.any.is_empty() {
+            return None;
+        }
+        let groups: Vec<String> = self
+            .any
+            .iter()
+            .map(|group| {
+                let terms: Vec<String> = group.iter().map(Term::to_fts).collect();
+                format!("({})", terms.join(" AND "))
+            })
+            .collect();
+        Some(groups.join(" OR "))
+    }
+
+    /// The rows to leave out, in FTS5 query syntax, if any are excluded.
+    fn negative(&self) -> Option<String> {
+        if self.not.is_empty() {
+            return None;
+        }
+        let terms: Vec<String> = self.not.iter().map(Term::to_fts).collect();
+        Some(format!("({})", terms.join(" OR ")))
+    }
+
+    /// The indexes of the rows in `urls` that match, in the order of `urls`.
+    pub fn matching(&self, urls: &[Url]) -> Result<Vec<usize>> {
+        self.run(&urls.iter().collect::<Vec<_>>())
+            .map_err(Error::Search)
+    }
+
+    /// Search `urls` with a search table of their own, for rows the index does not have.
+    fn run(&self, urls: &[&Url]) -> rusqlite::Result<Vec<usize>> {
+        let mut conn = Connection::open_in_memory()?;
+        conn.execute_batch("CREATE VIRTUAL TABLE search USING fts5(title, url, text)")?;
+        let tx = conn.transaction()?;
+        {
+            let mut insert =
+                tx.prepare("INSERT INTO search (rowid, title, url, text) VALUES (?1, ?2, ?3, ?4)")?;
+            for (i, u) in urls.iter().enumerate() {
+                insert.execute((i as i64, &u.title, &u.url, u.text.as_deref().unwrap_or("")))?;
+            }
+        }
+        tx.commit()?;
+
+        let mut matching: Vec<usize> = self
+            .matching_in(&conn, "rowid")?
+            .into_iter()
+            .map(|i: i64| i as usize)
+            .collect();
+        matching.sort_unstable();
+        Ok(matching)
+    }
+
+    /// `column` of the matching rows of the FTS5 table `search` in `conn`, in no particular order.
+    pub(crate) fn matching_in<T: FromSql>(
+        &self,
+        conn: &Connection,
+        column: &str,
+    ) -> rusqlite::Result<Vec<T>> {
+        let select = format!("SELECT {} FROM search", column);
+        let matches = format!("{} WHERE search MATCH ?", select);
+        // FTS5 cannot match everything but something, so the excluded rows are subtracted
+        let (sql, exprs) = match (self.positive(), self.negative()) {
+            (Some(positive), Some(negative)) => (
+                format!("{} EXCEPT {}", matches, matches),
+                vec![positive, negative],
+            ),
+            (Some(positive), None) => (matches, vec![positive]),
+            (None, Some(negative)) => (format!("{} EXCEPT {}", select, matches), vec![negative]),
+            (None, None) => (select, Vec::new()),
+        };
+        let mut stmt = conn.prepare(&sql)?;
+        stmt.query_map(params_from_iter(exprs), |row| row.get(0))?
+            .collect()
+    }
+
+    /// Keep only the rows of `urls` that match, in their current order.
+    ///
+    /// Pages in `index` are looked up in its search table, which holds their stored text; the
+    /// rest, such as open tabs the index has not seen, are searched on their own.
+    pub fn retain(&self, urls: &mut Vec<Url>, index: Option<&Index>) -> Result<()> {
+        let (indexed, found) = match index {
+            Some(index) => (index.indexed_urls()?, index.search(self)?),
+            None => Default::default(),
+        };
+        let rest: Vec<&Url> = urls.iter().filter(|u| !indexed.contains(&u.url)).collect();
+        let mut rest_matching = vec![false; rest.len()];
+        for i in self.run(&rest).map_err(Error::Search)? {
+            rest_matching[i] = true;
+        }
+        let mut rest_matching = rest_matching.into_iter();
+        urls.retain(|u| {
+            if indexed.contains(&u.url) {
+                found.contains(&u.url)
+            } else {
+                rest_matching.next().unwrap_or(false)
+            }
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(title: &str, url: &str) -> Url {
+        Url {
+            title: title.to_string(),
+            url: url.to_string(),
+            ..Url::default()
+        }
+    }
+
+    fn titles(urls: &[Url], query: &str) -> Vec<String> {
+        let query: Query = query.parse().unwrap();
+        query
+            .matching(urls)
+            .unwrap()
+            .into_iter()
+            .map(|i| urls[i].title.clone())
+            .collect()
+    }
+
+    #[test]
+    fn phrases_fields_and_exclusions() {
+        let mut urls = vec![
+            page(
+                "Incident review: payments",
+                "https://docs.example.com/ir/42",
+            ),
+            page(
+                "Incident review: payments",
+                "https://staging.docs.example.com/ir/42",
+            ),
+            page("Review of the incident", "https://docs.example.com/ir/43"),
+            page("Grafana", "https://grafana.example.com/d/incident-review"),
+        ];
+        urls[3].text = Some("Payments latency by region".to_string());
+        assert_eq!(
+            titles(&urls, r#"title:"incident review" -staging"#),
+            ["Incident review: payments"]
+        );
+        assert_eq!(titles(&urls, "url:grafana OR ir/43").len(), 2);
+        assert_eq!(titles(&urls, "graf*"), ["Grafana"]);
+        assert_eq!(titles(&urls, "-url:docs"), ["Grafana"]);
+        assert_eq!(titles(&urls, "TITLE:review -url:staging docs").len(), 2);
+        assert_eq!(titles(&urls, "text:latency"), ["Grafana"]);
+        assert_eq!(titles(&urls, "payments -text:payments").len(), 2);
+    }
+
+    #[test]
+    fn translates_to_quoted_fts5() {
+        let query: Query = r#"url:grafana.example.com dash* OR say"hi -x"#.parse().unwrap();
+        assert_eq!(
+            query.positive().unwrap(),
+            r#"(url : "grafana.example.com" AND "dash" *) OR ("say""hi")"#
+        );
+        assert_eq!(query.negative().unwrap(), r#"("x")"#);
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        for query in [
+            "",
+            "OR a",
+            "a OR",
+            "a OR OR b",
+            "a OR -b",
+            "title:",
+            "\"open",
+            "*",
+        ] {
+            assert!(query.parse::<Query>().is_err(), "{:?}", query);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+/// A shell `fuhl init` can print a widget for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl std::str::FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell '{}', expected bash, zsh or fish", s)),
+        }
+    }
+}
+
+// Each widget runs the picker with the print action, quotes what it prints for the shell and
+// inserts it at the cursor. The picker draws on the terminal itself, so only the selection
+// reaches the command substitution.
+
+const BASH: &str = r#"# fuhl: Ctrl-X Ctrl-U inserts URLs picked from browser history at the cursor
+__fuhl_insert_url() {
+  local selected
+  selected=$(command fuhl --multi --action print | while IFS= read -r url; do printf '%q ' "$url"; done)
+  READLINE_LINE="${READLINE_LINE:0:READLINE_POINT}${selected}${READLINE_LINE:READLINE_POINT}"
+  READLINE_POINT=$((READLINE_POINT + ${#selected}))
+}
+bind -m emacs-standard -x '"\C-x\C-u": __fuhl_insert_url'
+bind -m vi-command -x '"\C-x\C-u": __fuhl_insert_url'
+bind -m vi-insert -x '"\C-x\C-u": __fuhl_insert_url'
+"#;
+
+const ZSH: &str = r#"# fuhl: Ctrl-X Ctrl-U inserts URLs picked from browser history at the cursor
+fuhl-insert-url() {
+  local url selected
+  for url in ${(f)"$(command fuhl --multi --action print)"}; do
+    selected+="${(q)url} "
+  done
+  LBUFFER+=$selected
+  zle reset-prompt
+}
+zle -N fuhl-insert-url
+bindkey -M emacs '^X^U' fuhl-insert-url
+bindkey -M vicmd '^X^U' fuhl-insert-url
+bindkey -M viins '^X^U' fuhl-insert-url
+"#;
+
+const FISH: &str = r#"# fuhl: Ctrl-X Ctrl-U inserts URLs picked from browser history at the cursor
+function fuhl_insert_url -d 'Insert URLs picked from browser history'
+    set -l urls (command fuhl --multi --action print)
+    if test (count $urls) -gt 0
+        commandline -i -- (string join ' ' -- (string escape -- $urls))' '
+    end
+    commandline -f repaint
+end
+bind \cx\cu fuhl_insert_url
+bind -M insert \cx\cu fuhl_insert_url
+"#;
+
+/// The script that defines and binds the widget in `shell`.
+pub fn widget(shell: Shell) -> &'static str {
+    match shell {
+        Shell::Bash => BASH,
+        Shell::Zsh => ZSH,
+        Shell::Fish => FISH,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    const SHELLS: [(Shell, &str, &str); 3] = [
+        (Shell::Bash, "bash", "-n"),
+        (Shell::Zsh, "zsh", "-n"),
+        (Shell::Fish, "fish", "--no-execute"),
+    ];
+
+    #[test]
+    fn widgets_bind_ctrl_x_ctrl_u_to_the_print_action() {
+        for (shell, _, _) in SHELLS {
+            let script = widget(shell);
+            assert!(
+                script.contains("fuhl --multi --action print"),
+                "{:?}",
+                shell
+            );
+            let binds = script
+                .lines()
+                .filter(|l| l.starts_with("bind"))
+                .collect::<Vec<_>>();
+            assert!(!binds.is_empty(), "{:?}", shell);
+            for bind in binds {
+                assert!(
+                    ["\\C-x\\C-u", "^X^U", "\\cx\\cu"]
+                        .iter()
+                        .any(|key| bind.contains(key)),
+                    "{}",
+                    bind
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn widgets_parse_in_installed_shells() {
+        for (shell, program, check) in SHELLS {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("widget");
+            std::fs::write(&path, widget(shell)).unwrap();
+            // Shells that aren't installed are skipped
+            let Ok(output) = Command::new(program).arg(check).arg(&path).output() else {
+                continue;
+            };
+            assert!(
+                output.status.success(),
+                "{} rejects its widget: {}",
+                program,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use rusqlite::{Connection, OpenFlags};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// SQLite sidecar files holding changes not yet written back to the main database.
+const SIDECARS: &[&str] = &["-wal", "-journal"];
+
+/// A private copy of a browser database, taken so the browser's lock doesn't get in the way.
+///
+/// The browser may be writing to its database at any moment, so it is never read in place: the
+/// database and its sidecars are copied into their own temporary directory, readable only by
+/// the current user, which is removed when the snapshot is dropped.
+pub struct Snapshot {
+    // Held for its Drop, which deletes the directory
+    _dir: TempDir,
+    path: PathBuf,
+    has_sidecars: bool,
+}
+
+impl Snapshot {
+    /// Copy `source` and any `-wal`/`-journal` sidecar next to it.
+    pub fn create(source: &Path) -> std::io::Result<Snapshot> {
+        let dir = tempfile::Builder::new().prefix("fuhl-").tempdir()?;
+        let path = dir.path().join("db");
+        copy_private(source, &path)?;
+
+        let mut has_sidecars = false;
+        for suffix in SIDECARS {
+            let sidecar = with_suffix(source, suffix);
+            if sidecar.is_file() {
+                copy_private(&sidecar, &with_suffix(&path, suffix))?;
+                has_sidecars = true;
+            }
+        }
+
+        Ok(Snapshot {
+            _dir: dir,
+            path,
+            has_sidecars,
+        })
+    }
+
+    /// Open the copy.
+    ///
+    /// Without sidecars nothing can change it, so it is opened read-only with `immutable=1` and
+    /// SQLite skips locking entirely. With sidecars it is opened read-write, letting SQLite
+    /// apply the WAL or roll back the journal in the private copy.
+    pub fn open(&self) -> rusqlite::Result<Connection> {
+        if self.has_sidecars {
+            return Connection::open(&self.path);
+        }
+        // '?', '#' and '%' in the temporary directory would end or escape the path in a URI
+        let path = self
+            .path
+            .to_string_lossy()
+            .replace('%', "%25")
+            .replace('?', "%3f")
+            .replace('#', "%23");
+        let uri = format!("file:{}?immutable=1", path);
+        Connection::open_with_flags(
+            uri,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI,
+        )
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Copy a file and make the copy readable and writable by the owner only.
+fn copy_private(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::copy(from, to)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(to, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_databases_with_their_pending_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("History");
+        // The browser keeps its connection open, so its latest changes are only in the WAL
+        let browser = Connection::open(&path).unwrap();
+        browser
+            .execute_batch(
+                "PRAGMA journal_mode = WAL;
+                 PRAGMA wal_autocheckpoint = 0;
+                 CREATE TABLE urls (id INTEGER PRIMARY KEY);
+                 INSERT INTO urls VALUES (1);",
+            )
+            .unwrap();
+        assert!(std::fs::metadata(with_suffix(&path, "-wal")).unwrap().len() > 0);
+
+        let snapshot = Snapshot::create(&path).unwrap();
+        assert_ne!(snapshot.path, path);
+        let conn = snapshot.open().unwrap();
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM urls", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // Later writes by the browser don't reach the snapshot
+        browser.execute("INSERT INTO urls VALUES (2)", []).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT count(*) FROM urls", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+}
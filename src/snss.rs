@@ -0,0 +1,455 @@
+use crate::history::{TabState, Url};
+use crate::time::Timestamp;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// Commands of Chrome's session files (`Sessions/Session_*`, `Current Session`).
+mod session {
+    pub const SET_TAB_WINDOW: u8 = 0;
+    pub const TAB_NAVIGATION_PATH_PRUNED_FROM_BACK: u8 = 5;
+    pub const UPDATE_TAB_NAVIGATION: u8 = 6;
+    pub const SET_SELECTED_NAVIGATION_INDEX: u8 = 7;
+    pub const TAB_CLOSED: u8 = 16;
+    pub const WINDOW_CLOSED: u8 = 17;
+}
+
+/// Commands of Chrome's recently closed tabs files (`Sessions/Tabs_*`, `Current Tabs`).
+mod tab_restore {
+    pub const UPDATE_TAB_NAVIGATION: u8 = 1;
+    pub const RESTORED_ENTRY: u8 = 2;
+    pub const SELECTED_NAVIGATION_IN_TAB: u8 = 4;
+}
+
+/// One page in a tab's back/forward list.
+struct Navigation {
+    tab: i32,
+    index: i32,
+    url: String,
+    title: String,
+    time: Timestamp,
+}
+
+/// A tab as the commands in a file build it up.
+#[derive(Default)]
+struct Tab {
+    window: Option<i32>,
+    selected: Option<i32>,
+    navigations: BTreeMap<i32, Navigation>,
+    time: Timestamp,
+    closed: bool,
+}
+
+impl Tab {
+    /// The page the tab shows: its selected navigation, or else its last.
+    fn current(&self, state: TabState) -> Option<Url> {
+        let nav = self
+            .selected
+            .and_then(|i| self.navigations.get(&i))
+            .or_else(|| self.navigations.values().next_back())?;
+        let time = nav.time.max(self.time);
+        Some(Url::tab(nav.url.clone(), nav.title.clone(), time, state))
+    }
+}
+
+/// The tabs open in a session file, the current page of each.
+pub fn read_open_tabs(data: &[u8]) -> Result<Vec<Url>, String> {
+    let mut tabs: HashMap<i32, Tab> = HashMap::new();
+    let mut closed_windows: HashSet<i32> = HashSet::new();
+    for (id, payload) in commands(data)? {
+        match id {
+            session::UPDATE_TAB_NAVIGATION => {
+                if let Some(nav) = navigation(payload) {
+                    tabs.entry(nav.tab)
+                        .or_default()
+                        .navigations
+                        .insert(nav.index, nav);
+                }
+            }
+            session::SET_SELECTED_NAVIGATION_INDEX => {
+                if let Some([tab, index]) = ints(payload) {
+                    tabs.entry(tab).or_default().selected = Some(index);
+                }
+            }
+            session::SET_TAB_WINDOW => {
+                if let Some([window, tab]) = ints(payload) {
+                    tabs.entry(tab).or_default().window = Some(window);
+                }
+            }
+            session::TAB_NAVIGATION_PATH_PRUNED_FROM_BACK => {
+                if let Some([tab, count]) = ints(payload) {
+                    tabs.entry(tab)
+                        .or_default()
+                        .navigations
+                        .retain(|&i, _| i < count);
+                }
+            }
+            session::TAB_CLOSED => {
+                if let Some([tab]) = ints(payload) {
+                    tabs.entry(tab).or_default().closed = true;
+                }
+            }
+            session::WINDOW_CLOSED => {
+                if let Some([window]) = ints(payload) {
+                    closed_windows.insert(window);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(tabs
+        .values()
+        .filter(|t| !t.closed && !t.window.is_some_and(|w| closed_windows.contains(&w)))
+        .filter_map(|t| t.current(TabState::Open))
+        .collect())
+}
+
+/// The recently closed tabs in a tab restore file, the page each was showing, leaving out
+/// those that have been reopened.
+pub fn read_closed_tabs(data: &[u8]) -> Result<Vec<Url>, String> {
+    let mut tabs: HashMap<i32, Tab> = HashMap::new();
+    for (id, payload) in commands(data)? {
+        match id {
+            tab_restore::SELECTED_NAVIGATION_IN_TAB => {
+                if let Some([tab, index]) = ints(payload) {
+                    let entry = tabs.entry(tab).or_default();
+                    entry.selected = Some(index);
+                    // Newer files add the time the tab was closed
+                    if let Some(time) = payload.get(8..16) {
+                        let micros = i64::from_le_bytes(time.try_into().expect("8 bytes"));
+                        entry.time = Timestamp::from_webkit_micros(micros);
+                    }
+                }
+            }
+            tab_restore::UPDATE_TAB_NAVIGATION => {
+                if let Some(nav) = navigation(payload) {
+                    tabs.entry(nav.tab)
+                        .or_default()
+                        .navigations
+                        .insert(nav.index, nav);
+                }
+            }
+            tab_restore::RESTORED_ENTRY => {
+                if let Some([entry]) = ints(payload) {
+                    tabs.remove(&entry);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(tabs
+        .values()
+        .filter_map(|t| t.current(TabState::Closed))
+        .collect())
+}
+
+/// Split a file into (command id, payload) pairs after checking its header.
+///
+/// A command cut short at the end, as when the browser is writing the file, ends the list.
+fn commands(data: &[u8]) -> Result<Vec<(u8, &[u8])>, String> {
+    if data.get(..4) != Some(b"SNSS") {
+        return Err("not an SNSS file".to_string());
+    }
+    let version = data
+        .get(4..8)
+        .map(|v| i32::from_le_bytes(v.try_into().expect("4 bytes")))
+        .ok_or_else(|| "truncated header".to_string())?;
+    // Versions 2 and 4 are encrypted
+    if version != 1 && version != 3 {
+        return Err(format!("unsupported SNSS version {}", version));
+    }
+
+    let mut commands = Vec::new();
+    let mut i = 8;
+    while let Some(size) = data.get(i..i + 2) {
+        let size = u16::from_le_bytes([size[0], size[1]]) as usize;
+        let Some(command) = data.get(i + 2..i + 2 + size).filter(|c| !c.is_empty()) else {
+            break;
+        };
+        commands.push((command[0], &command[1..]));
+        i += 2 + size;
+    }
+    Ok(commands)
+}
+
+/// The leading 32-bit integers of a fixed-size payload.
+fn ints<const N: usize>(payload: &[u8]) -> Option<[i32; N]> {
+    let mut values = [0; N];
+    for (n, value) in values.iter_mut().enumerate() {
+        let bytes = payload.get(n * 4..n * 4 + 4)?;
+        *value = i32::from_le_bytes(bytes.try_into().ok()?);
+    }
+    Some(values)
+}
+
+/// Decode a pickled navigation entry: tab id, index, URL, title, and after a few fields fuhl
+/// has no use for, the time of the visit.
+fn navigation(payload: &[u8]) -> Option<Navigation> {
+    let mut pickle = Pickle::new(payload)?;
+    let tab = pickle.int()?;
+    let index = pickle.int()?;
+    let url = pickle.string()?;
+    let title = pickle.string16()?;
+    // Page state, transition, type mask, referrer, referrer policy, original request URL and
+    // whether the user agent was overridden come before the time, which older files lack
+    let time = (|| {
+        pickle.string()?;
+        pickle.int()?;
+        pickle.int()?;
+        pickle.string()?;
+        pickle.int()?;
+        pickle.string()?;
+        pickle.int()?;
+        pickle.int64()
+    })();
+    Some(Navigation {
+        tab,
+        index,
+        url,
+        title,
+        time: time.map(Timestamp::from_webkit_micros).unwrap_or_default(),
+    })
+}
+
+/// Chromium's `base::Pickle`: a 32-bit payload size, then fields padded to 4 bytes.
+struct Pickle<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Pickle<'a> {
+    fn new(payload: &'a [u8]) -> Option<Pickle<'a>> {
+        let size = u32::from_le_bytes(payload.get(..4)?.try_into().ok()?) as usize;
+        // Trust the header only as far as the payload goes
+        let data = &payload[4..];
+        Some(Pickle {
+            data: &data[..size.min(data.len())],
+            pos: 0,
+        })
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len.div_ceil(4) * 4;
+        Some(bytes)
+    }
+
+    fn int(&mut self) -> Option<i32> {
+        Some(i32::from_le_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    fn int64(&mut self) -> Option<i64> {
+        Some(i64::from_le_bytes(self.bytes(8)?.try_into().ok()?))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let len = usize::try_from(self.int()?).ok()?;
+        Some(String::from_utf8_lossy(self.bytes(len)?).into_owned())
+    }
+
+    fn string16(&mut self) -> Option<String> {
+        let len = usize::try_from(self.int()?).ok()?;
+        let units: Vec<u16> = self
+            .bytes(len.checked_mul(2)?)?
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        Some(String::from_utf16_lossy(&units))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds SNSS files the way Chrome writes them.
+    struct File(Vec<u8>);
+
+    impl File {
+        fn new() -> File {
+            let mut data = b"SNSS".to_vec();
+            data.extend_from_slice(&1i32.to_le_bytes());
+            File(data)
+        }
+
+        fn command(mut self, id: u8, payload: &[u8]) -> File {
+            self.0
+                .extend_from_slice(&(payload.len() as u16 + 1).to_le_bytes());
+            self.0.push(id);
+            self.0.extend_from_slice(payload);
+            self
+        }
+
+        fn ints(self, id: u8, values: &[i32]) -> File {
+            let payload: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+            self.command(id, &payload)
+        }
+
+        fn navigation(self, id: u8, tab: i32, index: i32, url: &str, time: i64) -> File {
+            fn pad(data: &mut Vec<u8>) {
+                while !data.len().is_multiple_of(4) {
+                    data.push(0);
+                }
+            }
+            let mut body = Vec::new();
+            body.extend_from_slice(&tab.to_le_bytes());
+            body.extend_from_slice(&index.to_le_bytes());
+            body.extend_from_slice(&(url.len() as i32).to_le_bytes());
+            body.extend_from_slice(url.as_bytes());
+            pad(&mut body);
+            let title: Vec<u16> = format!("Title of {}", url).encode_utf16().collect();
+            body.extend_from_slice(&(title.len() as i32).to_le_bytes());
+            body.extend(title.iter().flat_map(|u| u.to_le_bytes()));
+            pad(&mut body);
+            // Empty page state, transition, type mask, empty referrer, policy, empty original
+            // URL and user agent override
+            for value in [0i32; 7] {
+                body.extend_from_slice(&value.to_le_bytes());
+            }
+            body.extend_from_slice(&time.to_le_bytes());
+
+            let mut payload = (body.len() as u32).to_le_bytes().to_vec();
+            payload.extend_from_slice(&body);
+            self.command(id, &payload)
+        }
+    }
+
+    fn urls(mut tabs: Vec<Url>) -> Vec<(String, String, i64)> {
+        tabs.sort_by(|a, b| a.url.cmp(&b.url));
+        tabs.into_iter()
+            .map(|u| (u.url, u.title, u.last_visit_time.webkit_micros()))
+            .collect()
+    }
+
+    #[test]
+    fn open_tabs_show_their_selected_page() {
+        let file = File::new()
+            .ints(session::SET_TAB_WINDOW, &[1, 10])
+            .navigation(
+                session::UPDATE_TAB_NAVIGATION,
+                10,
+                0,
+                "https://a.example/",
+                100,
+            )
+            .navigation(
+                session::UPDATE_TAB_NAVIGATION,
+                10,
+                1,
+                "https://b.example/",
+                200,
+            )
+            .ints(session::SET_SELECTED_NAVIGATION_INDEX, &[10, 0])
+            // Closed tab
+            .ints(session::SET_TAB_WINDOW, &[1, 11])
+            .navigation(
+                session::UPDATE_TAB_NAVIGATION,
+                11,
+                0,
+                "https://c.example/",
+                300,
+            )
+            .ints(session::TAB_CLOSED, &[11, 0, 0])
+            // Tab in a closed window
+            .ints(session::SET_TAB_WINDOW, &[2, 12])
+            .navigation(
+                session::UPDATE_TAB_NAVIGATION,
+                12,
+                0,
+                "https://d.example/",
+                400,
+            )
+            .ints(session::WINDOW_CLOSED, &[2, 0, 0])
+            // Forward history pruned away
+            .ints(session::SET_TAB_WINDOW, &[1, 13])
+            .navigation(
+                session::UPDATE_TAB_NAVIGATION,
+                13,
+                0,
+                "https://e.example/",
+                500,
+            )
+            .navigation(
+                session::UPDATE_TAB_NAVIGATION,
+                13,
+                1,
+                "https://f.example/",
+                600,
+            )
+            .ints(session::TAB_NAVIGATION_PATH_PRUNED_FROM_BACK, &[13, 1])
+            // Cut short while being written
+            .command(session::UPDATE_TAB_NAVIGATION, &[]);
+        let mut data = file.0;
+        data.truncate(data.len() - 1);
+
+        let tabs = read_open_tabs(&data).unwrap();
+        assert!(tabs.iter().all(|t| t.tab == Some(TabState::Open)));
+        assert_eq!(
+            urls(tabs),
+            [
+                (
+                    "https://a.example/".to_string(),
+                    "Title of https://a.example/".to_string(),
+                    100
+                ),
+                (
+                    "https://e.example/".to_string(),
+                    "Title of https://e.example/".to_string(),
+                    500
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn closed_tabs_leave_out_reopened_ones() {
+        // Newer files record when the tab was closed
+        let closed_at: Vec<u8> = [22i32.to_le_bytes(), 0i32.to_le_bytes()]
+            .concat()
+            .into_iter()
+            .chain(700i64.to_le_bytes())
+            .collect();
+        let data = File::new()
+            .ints(tab_restore::SELECTED_NAVIGATION_IN_TAB, &[20, 0])
+            .navigation(
+                tab_restore::UPDATE_TAB_NAVIGATION,
+                20,
+                0,
+                "https://a.example/",
+                100,
+            )
+            .ints(tab_restore::SELECTED_NAVIGATION_IN_TAB, &[21, 0])
+            .navigation(
+                tab_restore::UPDATE_TAB_NAVIGATION,
+                21,
+                0,
+                "https://b.example/",
+                200,
+            )
+            .ints(tab_restore::RESTORED_ENTRY, &[21])
+            .command(tab_restore::SELECTED_NAVIGATION_IN_TAB, &closed_at)
+            .navigation(
+                tab_restore::UPDATE_TAB_NAVIGATION,
+                22,
+                0,
+                "https://c.example/",
+                100,
+            )
+            .0;
+
+        let tabs = read_closed_tabs(&data).unwrap();
+        assert!(tabs.iter().all(|t| t.tab == Some(TabState::Closed)));
+        let urls = urls(tabs);
+        assert_eq!(urls.len(), 2);
+        assert_eq!((urls[0].0.as_str(), urls[0].2), ("https://a.example/", 100));
+        assert_eq!((urls[1].0.as_str(), urls[1].2), ("https://c.example/", 700));
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(read_open_tabs(b"SQLite format 3\0").is_err());
+        let mut encrypted = b"SNSS".to_vec();
+        encrypted.extend_from_slice(&2i32.to_le_bytes());
+        assert!(read_closed_tabs(&encrypted).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use crate::discover::Profile;
+use crate::error::{Error, Result};
+use crate::history::{self, Browser, Source, Url};
+use crate::snapshot::Snapshot;
+use crate::time::Timestamp;
+use rusqlite::Connection;
+use std::collections::HashSet;
+
+/// Somewhere history rows come from.
+///
+/// Browser profiles ([`Profile`]) are sources; embedders can add their own, such as a team's
+/// pinned links. Rows should carry their [`Source`] so they can be told apart after merging.
+///
+/// Problems that leave out part of a source, such as rows that cannot be decoded or unreadable
+/// bookmarks, go into `warnings` rather than failing the whole read.
+pub trait HistorySource {
+    /// One row per page, bookmarks included.
+    fn read_urls(&self, warnings: &mut Vec<Error>) -> Result<Vec<Url>>;
+
+    /// One row per visit, newest first, see [`Browser::read_visit_log`]. Sources that keep no
+    /// visits have none.
+    fn read_visits(&self, _warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        Ok(Vec::new())
+    }
+
+    /// One row per search typed into a search engine, see [`Browser::read_searches`]. Sources
+    /// that keep no searches have none.
+    fn read_searches(&self, _warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        Ok(Vec::new())
+    }
+
+    /// One row per page that describes itself, with the description as its
+    /// [`text`](Url::text), see [`Browser::read_descriptions`]. Sources that keep no
+    /// descriptions have none.
+    fn read_descriptions(&self, _warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        Ok(Vec::new())
+    }
+}
+
+/// What [`load_all`] lists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// One row per page.
+    #[default]
+    Pages,
+    /// One row per visit.
+    Visits,
+    /// One row per distinct search.
+    Searches,
+}
+
+impl HistorySource for Profile {
+    fn read_urls(&self, warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        self.read_urls_since(Timestamp::default(), warnings)
+    }
+
+    fn read_visits(&self, warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        self.read_database(warnings, |conn, browser, skipped, _| {
+            browser.read_visit_log(conn, skipped)
+        })
+    }
+
+    fn read_searches(&self, warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        self.read_database(warnings, |conn, browser, skipped, _| {
+            browser.read_searches(conn, skipped)
+        })
+    }
+
+    fn read_descriptions(&self, warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        self.read_database(warnings, |conn, browser, skipped, _| {
+            browser.read_descriptions(conn, skipped)
+        })
+    }
+}
+
+impl Profile {
+    /// The pages last visited at or after `since`, and every bookmark.
+    pub fn read_urls_since(&self, since: Timestamp, warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        self.read_database(warnings, |conn, browser, skipped, warnings| {
+            self.read_pages(conn, browser, since, skipped, warnings)
+        })
+    }
+
+    /// The same as [`read_urls_since`](Profile::read_urls_since), along with the URL of every
+    /// page the browser still has, so that pages it has since expired or deleted can be dropped.
+    pub fn read_urls_and_pages_since(
+        &self,
+        since: Timestamp,
+        warnings: &mut Vec<Error>,
+    ) -> Result<(Vec<Url>, HashSet<String>)> {
+        let mut pages = HashSet::new();
+        let urls = self.read_database(warnings, |conn, browser, skipped, warnings| {
+            pages = browser.read_page_urls(conn)?;
+            self.read_pages(conn, browser, since, skipped, warnings)
+        })?;
+        Ok((urls, pages))
+    }
+
+    fn read_pages(
+        &self,
+        conn: &Connection,
+        browser: Browser,
+        since: Timestamp,
+        skipped: &mut Vec<rusqlite::Error>,
+        warnings: &mut Vec<Error>,
+    ) -> rusqlite::Result<Vec<Url>> {
+        let mut urls = browser.read_urls(conn, since, skipped)?;
+        // Bookmarks are a bonus; history without them is still worth showing
+        match browser.read_bookmarks(conn, &self.path, skipped) {
+            Ok(mut bookmarks) => urls.append(&mut bookmarks),
+            Err(e) => warnings.push(e),
+        }
+        Ok(urls)
+    }
+
+    /// Run `read` on a private snapshot of the profile's database and tag the rows it returns
+    /// with the profile. Rows it skips become warnings.
+    fn read_database(
+        &self,
+        warnings: &mut Vec<Error>,
+        read: impl FnOnce(
+            &Connection,
+            Browser,
+            &mut Vec<rusqlite::Error>,
+            &mut Vec<Error>,
+        ) -> rusqlite::Result<Vec<Url>>,
+    ) -> Result<Vec<Url>> {
+        let path = &self.path;
+        if !path.exists() {
+            return Err(Error::SourceNotFound(path.clone()));
+        }
+        let sqlite = |source| Error::Sqlite {
+            path: path.clone(),
+            source,
+        };
+
+        // Declared before the connection so the copy outlives it
+        let snapshot = Snapshot::create(path).map_err(|source| Error::Copy {
+            path: path.clone(),
+            source,
+        })?;
+        let conn = snapshot.open().map_err(sqlite)?;
+        let browser =
+            Browser::detect(&conn).ok_or_else(|| Error::SchemaUnsupported(path.clone()))?;
+        let mut skipped = Vec::new();
+        let mut urls = read(&conn, browser, &mut skipped, warnings).map_err(sqlite)?;
+        warnings.extend(skipped.into_iter().map(sqlite));
+
+        let source = Source {
+            browser: self.browser.unwrap_or(browser.name()).to_string(),
+            profile: self.profile.clone(),
+            path: path.clone(),
+        };
+        for u in &mut urls {
+            u.sources.push(source.clone());
+        }
+        Ok(urls)
+    }
+}
+
+/// Read every source: pages merged across sources, every visit, or searches merged by term.
+///
+/// A source that fails is added to `warnings` and skipped; it is only an error when none can
+/// be read.
+pub fn load_all(
+    sources: &[Box<dyn HistorySource>],
+    mode: Mode,
+    warnings: &mut Vec<Error>,
+) -> Result<Vec<Url>> {
+    if sources.is_empty() {
+        return Err(Error::NoSources);
+    }
+
+    let mut rows: Vec<Url> = Vec::new();
+    let mut failed = Vec::new();
+    let mut loaded = 0;
+    for source in sources {
+        let read = match mode {
+            Mode::Pages => source.read_urls(warnings),
+            Mode::Visits => source.read_visits(warnings),
+            Mode::Searches => source.read_searches(warnings),
+        };
+        match read {
+            Ok(mut urls) => {
+                rows.append(&mut urls);
+                loaded += 1;
+            }
+            Err(e) if sources.len() > 1 => failed.push(e),
+            Err(e) => return Err(e),
+        }
+    }
+    if loaded == 0 {
+        let mut failed = failed.into_iter();
+        let first = failed.next().unwrap_or(Error::NoSources);
+        warnings.extend(failed);
+        return Err(first);
+    }
+    warnings.append(&mut failed);
+
+    Ok(match mode {
+        Mode::Pages => history::merge(rows),
+        // Visits stay one row each
+        Mode::Visits => rows,
+        Mode::Searches => history::merge_searches(rows),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Pinned;
+
+    impl HistorySource for Pinned {
+        fn read_urls(&self, warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+            warnings.push(Error::Custom("one row was left out".into()));
+            Ok(vec![Url {
+                url: "https://pinned.example/".to_string(),
+                ..Url::default()
+            }])
+        }
+    }
+
+    struct Missing;
+
+    impl HistorySource for Missing {
+        fn read_urls(&self, _warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+            Err(Error::SourceNotFound("/nonexistent/History".into()))
+        }
+    }
+
+    #[test]
+    fn failures_are_returned_as_warnings() {
+        let sources: Vec<Box<dyn HistorySource>> = vec![Box::new(Pinned), Box::new(Missing)];
+        let mut warnings = Vec::new();
+        let urls = load_all(&sources, Mode::Pages, &mut warnings).unwrap();
+        assert_eq!(urls.len(), 1);
+        assert!(matches!(
+            warnings.as_slice(),
+            [Error::Custom(_), Error::SourceNotFound(_)]
+        ));
+
+        let sources: Vec<Box<dyn HistorySource>> = vec![Box::new(Missing), Box::new(Missing)];
+        let mut warnings = Vec::new();
+        let error = load_all(&sources, Mode::Pages, &mut warnings).unwrap_err();
+        assert!(matches!(error, Error::SourceNotFound(_)));
+        assert_eq!(warnings.len(), 1);
+    }
+}
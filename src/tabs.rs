@@ -0,0 +1,184 @@
+use crate::discover::Profile;
+use crate::error::{Error, Result};
+use crate::history::{Source, Url};
+use crate::source::HistorySource;
+use crate::{firefox, mozlz4, snss};
+use std::path::{Path, PathBuf};
+
+/// The tabs open in a browser profile and the ones closed in it recently, read from its session
+/// files rather than its history database.
+///
+/// Chrome and Chromium-based browsers keep them in `Sessions/Session_*` and `Sessions/Tabs_*`
+/// next to `History`, Firefox in `sessionstore-backups/recovery.jsonlz4` next to
+/// `places.sqlite`. Other profiles have none.
+pub struct Tabs {
+    profile: Profile,
+}
+
+impl Tabs {
+    pub fn new(profile: Profile) -> Tabs {
+        Tabs { profile }
+    }
+}
+
+impl HistorySource for Tabs {
+    fn read_urls(&self, _warnings: &mut Vec<Error>) -> Result<Vec<Url>> {
+        let path = &self.profile.path;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let (browser, mut tabs) = match path.file_name().and_then(|n| n.to_str()) {
+            Some("History") => ("Chrome", read_chrome(dir)?),
+            Some("places.sqlite") => ("Firefox", read_firefox(dir)?),
+            _ => return Ok(Vec::new()),
+        };
+
+        let source = Source {
+            browser: self.profile.browser.unwrap_or(browser).to_string(),
+            profile: self.profile.profile.clone(),
+            path: path.clone(),
+        };
+        for u in &mut tabs {
+            u.sources.push(source.clone());
+        }
+        Ok(tabs)
+    }
+}
+
+/// Open tabs from the newest session file, and closed tabs from every tab restore file.
+fn read_chrome(dir: &Path) -> Result<Vec<Url>> {
+    let sessions = dir.join("Sessions");
+    let mut session_files = files_starting_with(&sessions, "Session_");
+    let mut tab_files = files_starting_with(&sessions, "Tabs_");
+    // Browsers before Chrome 86 keep them in the profile directory
+    if session_files.is_empty() && tab_files.is_empty() {
+        session_files = existing([dir.join("Current Session")]);
+        tab_files = existing([dir.join("Current Tabs")]);
+    }
+
+    let mut tabs = Vec::new();
+    // Files are named after the time they were started, so the newest sorts last
+    if let Some(path) = session_files.last() {
+        tabs.extend(parse(path, snss::read_open_tabs)?);
+    }
+    for path in &tab_files {
+        tabs.extend(parse(path, snss::read_closed_tabs)?);
+    }
+    Ok(tabs)
+}
+
+/// Tabs from the session store Firefox keeps while it runs, or else the one it writes on exit.
+fn read_firefox(dir: &Path) -> Result<Vec<Url>> {
+    let candidates = [
+        dir.join("sessionstore-backups").join("recovery.jsonlz4"),
+        dir.join("sessionstore.jsonlz4"),
+    ];
+    let Some(path) = existing(candidates).into_iter().next() else {
+        return Ok(Vec::new());
+    };
+    parse(&path, |data| {
+        let json = mozlz4::decompress(data)?;
+        let session = serde_json::from_slice(&json).map_err(|e| e.to_string())?;
+        Ok(firefox::read_session(&session))
+    })
+}
+
+fn parse(path: &Path, read: impl Fn(&[u8]) -> Result<Vec<Url>, String>) -> Result<Vec<Url>> {
+    let data = std::fs::read(path).map_err(|source| Error::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    read(&data).map_err(|message| Error::Session {
+        path: path.to_path_buf(),
+        message,
+    })
+}
+
+/// Files in `dir` whose names start with `prefix`, sorted by name.
+fn files_starting_with(dir: &Path, prefix: &str) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().starts_with(prefix))
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    files
+}
+
+fn existing(paths: impl IntoIterator<Item = PathBuf>) -> Vec<PathBuf> {
+    paths.into_iter().filter(|p| p.is_file()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::TabState;
+
+    /// A `.jsonlz4` file holding `json` as a single run of LZ4 literals.
+    fn jsonlz4(json: &str) -> Vec<u8> {
+        let mut file = b"mozLz40\0".to_vec();
+        file.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        file.push(0xF0);
+        let mut extra = json.len() - 15;
+        while extra >= 255 {
+            file.push(255);
+            extra -= 255;
+        }
+        file.push(extra as u8);
+        file.extend_from_slice(json.as_bytes());
+        file
+    }
+
+    fn tabs(path: PathBuf) -> Vec<Url> {
+        Tabs::new(Profile::from_path(path))
+            .read_urls(&mut Vec::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_firefox_session_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let profile = dir.path().join("x1y2.default");
+        std::fs::create_dir_all(profile.join("sessionstore-backups")).unwrap();
+        let session = r#"{"windows": [{"tabs": [{"entries": [{"url": "https://a.example/", "title": "A"}], "index": 1}]}]}"#;
+        std::fs::write(
+            profile
+                .join("sessionstore-backups")
+                .join("recovery.jsonlz4"),
+            jsonlz4(session),
+        )
+        .unwrap();
+
+        let places = profile.join("places.sqlite");
+        let urls = tabs(places.clone());
+        assert_eq!(urls.len(), 1);
+        assert_eq!(urls[0].url, "https://a.example/");
+        assert_eq!(urls[0].tab, Some(TabState::Open));
+        let source = &urls[0].sources[0];
+        assert_eq!(
+            (source.browser.as_str(), source.profile.as_str()),
+            ("Firefox", "x1y2.default")
+        );
+        assert_eq!(source.path, places);
+
+        std::fs::write(
+            profile
+                .join("sessionstore-backups")
+                .join("recovery.jsonlz4"),
+            b"not a session",
+        )
+        .unwrap();
+        let broken = Tabs::new(Profile::from_path(places)).read_urls(&mut Vec::new());
+        assert!(matches!(broken, Err(Error::Session { .. })));
+    }
+
+    #[test]
+    fn profiles_without_session_files_have_no_tabs() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(tabs(dir.path().join("History")).is_empty());
+        assert!(tabs(dir.path().join("places.sqlite")).is_empty());
+        assert!(tabs(dir.path().join("History.db")).is_empty());
+    }
+}
@@ -0,0 +1,216 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Microseconds between 1601-01-01 (Chrome's epoch) and 1970-01-01 (the Unix epoch).
+pub const UNIX_EPOCH_OFFSET_MICROS: i64 = 11_644_473_600_000_000;
+
+/// Seconds between 1970-01-01 and 2001-01-01, the Core Data epoch Safari uses.
+const CORE_DATA_EPOCH_OFFSET_SECS: f64 = 978_307_200.0;
+
+pub const MICROS_PER_DAY: i64 = 86_400_000_000;
+
+/// A point in time, whichever browser recorded it.
+///
+/// Held the way Chrome stores it, as microseconds since 1601-01-01 UTC. The default, 1601
+/// itself, stands for "never".
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Timestamp(i64);
+
+impl Timestamp {
+    /// Chrome's `last_visit_time` and `visit_time`: microseconds since 1601-01-01.
+    pub const fn from_webkit_micros(micros: i64) -> Timestamp {
+        Timestamp(micros)
+    }
+
+    /// Firefox's `visit_date`: microseconds since 1970-01-01.
+    pub fn from_unix_micros(micros: i64) -> Timestamp {
+        Timestamp(micros + UNIX_EPOCH_OFFSET_MICROS)
+    }
+
+    /// Safari's `visit_time`: seconds since 2001-01-01.
+    pub fn from_core_data_secs(secs: f64) -> Timestamp {
+        Timestamp::from_unix_micros(((secs + CORE_DATA_EPOCH_OFFSET_SECS) * 1_000_000.0) as i64)
+    }
+
+    pub fn now() -> Timestamp {
+        let since_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Timestamp::from_unix_micros(since_unix.as_micros() as i64)
+    }
+
+    pub fn webkit_micros(self) -> i64 {
+        self.0
+    }
+
+    pub fn unix_micros(self) -> i64 {
+        self.0 - UNIX_EPOCH_OFFSET_MICROS
+    }
+
+    pub fn core_data_secs(self) -> f64 {
+        self.unix_micros() as f64 / 1_000_000.0 - CORE_DATA_EPOCH_OFFSET_SECS
+    }
+
+    pub fn is_never(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whole days since 1601-01-01 UTC, for grouping visits by day.
+    pub fn day(self) -> i64 {
+        self.0.div_euclid(MICROS_PER_DAY)
+    }
+
+    /// Days from `self` until `later`, never negative.
+    pub fn days_until(self, later: Timestamp) -> f64 {
+        (later.0 - self.0).max(0) as f64 / MICROS_PER_DAY as f64
+    }
+
+    pub fn local(self) -> Option<DateTime<Local>> {
+        if self.is_never() {
+            return None;
+        }
+        DateTime::from_timestamp_micros(self.0 - UNIX_EPOCH_OFFSET_MICROS)
+            .map(|t| t.with_timezone(&Local))
+    }
+
+    fn from_local(t: DateTime<Local>) -> Timestamp {
+        Timestamp::from_unix_micros(t.timestamp_micros())
+    }
+
+    /// `duration` earlier, or `None` if that is before the earliest time a timestamp can hold.
+    pub fn checked_sub(self, duration: Duration) -> Option<Timestamp> {
+        let micros = i64::try_from(duration.as_micros()).ok()?;
+        self.0.checked_sub(micros).map(Timestamp)
+    }
+}
+
+/// Panics if the result is out of range, as subtracting from [`std::time::Instant`] does; see
+/// [`Timestamp::checked_sub`].
+impl std::ops::Sub<Duration> for Timestamp {
+    type Output = Timestamp;
+
+    fn sub(self, rhs: Duration) -> Timestamp {
+        self.checked_sub(rhs)
+            .expect("overflow when subtracting duration from timestamp")
+    }
+}
+
+/// Local date and time to the minute, or "unknown" for never.
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.local() {
+            Some(t) => write!(f, "{}", t.format("%Y-%m-%d %H:%M")),
+            None => write!(f, "unknown"),
+        }
+    }
+}
+
+/// Local midnight at the start of today.
+pub fn start_of_today() -> Timestamp {
+    let today = Local::now().date_naive();
+    local_midnight(today).unwrap_or_else(Timestamp::now)
+}
+
+fn local_midnight(date: NaiveDate) -> Option<Timestamp> {
+    Local
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .map(Timestamp::from_local)
+}
+
+/// Parse a point in time for `--since` and `--before`.
+///
+/// Accepts an age such as `30m`, `12h`, `2d` or `3w` counted back from `now`, a local date
+/// such as `2026-01-01` meaning its midnight, or a local `2026-01-01T09:30`.
+pub fn parse_when(s: &str, now: Timestamp) -> Result<Timestamp, String> {
+    let s = s.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return local_midnight(date).ok_or_else(|| format!("'{}' does not exist locally", s));
+    }
+    for format in ["%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(t) = chrono::NaiveDateTime::parse_from_str(s, format) {
+            return Local
+                .from_local_datetime(&t)
+                .earliest()
+                .map(Timestamp::from_local)
+                .ok_or_else(|| format!("'{}' does not exist locally", s));
+        }
+    }
+
+    let unit_at = s.len().saturating_sub(1);
+    let (count, unit) = (s.get(..unit_at), s.get(unit_at..));
+    let count: Option<u64> = count.and_then(|c| c.parse().ok());
+    let unit_secs = match (count, unit) {
+        (Some(_), Some("m")) => 60,
+        (Some(_), Some("h")) => 3_600,
+        (Some(_), Some("d")) => 86_400,
+        (Some(_), Some("w")) => 7 * 86_400,
+        _ => {
+            return Err(format!(
+                "invalid time '{}', expected an age like 2d or a date like 2026-01-01",
+                s
+            ));
+        }
+    };
+    count
+        .and_then(|n| n.checked_mul(unit_secs))
+        .and_then(|secs| now.checked_sub(Duration::from_secs(secs)))
+        .ok_or_else(|| format!("age '{}' out of range", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browser_epochs_agree() {
+        // 2026-01-01T00:00:00Z in each browser's representation
+        let chrome = Timestamp::from_webkit_micros(13_411_699_200_000_000);
+        let firefox = Timestamp::from_unix_micros(1_767_225_600_000_000);
+        let safari = Timestamp::from_core_data_secs(788_918_400.0);
+        assert_eq!(chrome, firefox);
+        assert_eq!(chrome, safari);
+    }
+
+    #[test]
+    fn parses_ages() {
+        let now = Timestamp::from_webkit_micros(13_411_699_200_000_000);
+        assert_eq!(
+            parse_when("2d", now).unwrap(),
+            Timestamp::from_webkit_micros(13_411_699_200_000_000 - 2 * MICROS_PER_DAY)
+        );
+        assert_eq!(
+            parse_when("90m", now).unwrap(),
+            Timestamp::from_webkit_micros(13_411_699_200_000_000 - 5_400_000_000)
+        );
+        assert!(parse_when("2y", now).is_err());
+        assert!(parse_when("d", now).is_err());
+    }
+
+    #[test]
+    fn rejects_ages_out_of_range() {
+        let now = Timestamp::from_webkit_micros(13_411_699_200_000_000);
+        for age in [
+            "999999999999999999d",
+            "18446744073709551615w",
+            "999999999999999m",
+        ] {
+            let error = parse_when(age, now).unwrap_err();
+            assert!(error.contains("out of range"), "{}: {}", age, error);
+        }
+        assert_eq!(now.checked_sub(Duration::MAX), None);
+    }
+
+    #[test]
+    fn parses_dates_as_local_midnight() {
+        let now = Timestamp::now();
+        let t = parse_when("2026-01-01", now).unwrap();
+        assert_eq!(t.to_string(), "2026-01-01 00:00");
+        let t = parse_when("2026-01-01T09:30", now).unwrap();
+        assert_eq!(t.to_string(), "2026-01-01 09:30");
+    }
+}